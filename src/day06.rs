@@ -1,7 +1,8 @@
-use std::collections::HashSet;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-enum Dir {
+pub enum Dir {
     North,
     East,
     South,
@@ -19,110 +20,156 @@ impl Dir {
     }
 }
 
-#[derive(Debug)]
-enum MapError {
-    Obstacle,
-    Bounds,
-}
-
-struct Guard {
-    position: (usize, usize),
+// Sorted obstacle indices per row and column, so the guard can jump straight to
+// the next obstacle instead of walking one cell at a time
+#[derive(Debug, Clone)]
+pub struct JumpTable {
     bounds: (usize, usize),
-    direction: Dir,
-    visited: HashSet<(Dir, (usize, usize))>
+    rows: Vec<Vec<usize>>,
+    cols: Vec<Vec<usize>>,
 }
 
-impl Guard {
-    fn new(start: (usize, usize), bounds: (usize, usize)) -> Self {
-        Self { position: start, bounds, direction: Dir::North, visited: HashSet::from([(Dir::North, (start))]) }
+impl JumpTable {
+    pub fn new(obstacles: &HashSet<(usize, usize)>, bounds: (usize, usize)) -> Self {
+        let mut table = Self {
+            bounds,
+            rows: vec![Vec::new(); bounds.1 + 1],
+            cols: vec![Vec::new(); bounds.0 + 1],
+        };
+        for &pos in obstacles {
+            table.insert(pos);
+        }
+
+        table
     }
 
-    fn test_loop(&self, obstacles: &HashSet<(usize, usize)>) -> bool {
-        let mut scout = Guard::new(self.position, self.bounds);
-        scout.direction = self.direction;
-        let mut scout_visited = HashSet::new();
-        let start = (self.direction, self.position);
+    pub fn contains(&self, pos: (usize, usize)) -> bool {
+        self.rows[pos.1].binary_search(&pos.0).is_ok()
+    }
 
-        loop {
-            match scout.look(obstacles) {
-                Ok(_) => {
-                        scout_visited.insert((scout.direction, scout.position));
-                        scout.step();
-                    },
-                Err(MapError::Obstacle) => scout.direction.right(),
-                Err(MapError::Bounds) => return false,
-            }
-            if scout.direction == start.0 && scout.position == start.1 {
-                let similar: HashSet<_> = self.visited.intersection(&scout_visited).collect();
-                return similar.len() > 0;
-            }
-            if scout_visited.contains(&(scout.direction, scout.position)) {
-                return false;
-            }
+    // Returns false if the obstacle was already there
+    pub fn insert(&mut self, pos: (usize, usize)) -> bool {
+        match self.rows[pos.1].binary_search(&pos.0) {
+            Ok(_) => false,
+            Err(idx) => {
+                self.rows[pos.1].insert(idx, pos.0);
+                let col = &mut self.cols[pos.0];
+                let idx = col.partition_point(|&row| row < pos.1);
+                col.insert(idx, pos.1);
+                true
+            },
         }
     }
 
-    // Look one position ahead return Ok if clear, or specific error
-    fn look(&self, obstacles: &HashSet<(usize, usize)>) -> Result<(usize, usize), MapError> {
-        match self.direction {
-            Dir::North => {
-                if self.position.1 == 0 {
-                    return Err(MapError::Bounds);
-                } else if obstacles.contains(&(self.position.0, self.position.1 - 1)) {
-                    return Err(MapError::Obstacle);
-                } else {
-                    Ok((self.position.0, self.position.1 - 1))
-                }
+    // Returns false if there was no obstacle to remove
+    pub fn remove(&mut self, pos: (usize, usize)) -> bool {
+        match self.rows[pos.1].binary_search(&pos.0) {
+            Ok(idx) => {
+                self.rows[pos.1].remove(idx);
+                let col = &mut self.cols[pos.0];
+                let idx = col.partition_point(|&row| row < pos.1);
+                col.remove(idx);
+                true
             },
-            Dir::East  => {
-                if self.position.0 == self.bounds.0 {
-                    return Err(MapError::Bounds);
-                } else if obstacles.contains(&(self.position.0 + 1, self.position.1)) {
-                    return Err(MapError::Obstacle);
-                } else {
-                    Ok((self.position.0 + 1, self.position.1))
-                }
+            Err(_) => false,
+        }
+    }
+
+    // Cell the guard stops on in front of the next obstacle, or None if the guard walks off the map
+    pub fn next_stop(&self, (x, y): (usize, usize), direction: Dir) -> Option<(usize, usize)> {
+        match direction {
+            Dir::North => {
+                let col = &self.cols[x];
+                let idx = col.partition_point(|&row| row < y);
+                if idx == 0 { None } else { Some((x, col[idx - 1] + 1)) }
             },
             Dir::South => {
-                if self.position.1 == self.bounds.1 {
-                    return Err(MapError::Bounds);
-                } else if obstacles.contains(&(self.position.0, self.position.1 + 1)) {
-                    return Err(MapError::Obstacle);
-                } else {
-                    Ok((self.position.0, self.position.1 + 1))
-                }
+                let col = &self.cols[x];
+                col.get(col.partition_point(|&row| row <= y)).map(|&row| (x, row - 1))
             },
             Dir::West  => {
-                if self.position.0 == 0 {
-                    return Err(MapError::Bounds);
-                } else if obstacles.contains(&(self.position.0 - 1, self.position.1)) {
-                    return Err(MapError::Obstacle);
-                } else {
-                    Ok((self.position.0 - 1, self.position.1))
-                }
+                let row = &self.rows[y];
+                let idx = row.partition_point(|&col| col < x);
+                if idx == 0 { None } else { Some((row[idx - 1] + 1, y)) }
+            },
+            Dir::East  => {
+                let row = &self.rows[y];
+                row.get(row.partition_point(|&col| col <= x)).map(|&col| (col - 1, y))
             },
         }
     }
 
-    fn step(&mut self) {
-        let new_pos = match self.direction {
-            Dir::North => (self.position.0, self.position.1 - 1),
-            Dir::East  => (self.position.0 + 1, self.position.1),
-            Dir::South => (self.position.0, self.position.1 + 1),
-            Dir::West  => (self.position.0 - 1, self.position.1),
-        };
-        self.position = new_pos;
-        self.visited.insert((self.direction, new_pos));
+    // Last cell on the map in a given direction
+    fn edge(&self, (x, y): (usize, usize), direction: Dir) -> (usize, usize) {
+        match direction {
+            Dir::North => (x, 0),
+            Dir::East  => (self.bounds.0, y),
+            Dir::South => (x, self.bounds.1),
+            Dir::West  => (0, y),
+        }
     }
+}
 
-    fn visited(&self) -> usize {
-        let mut count = HashSet::new();
+#[derive(Debug, PartialEq, Eq)]
+pub enum Patrol {
+    Exit,
+    // Turning points of the cycle, as the heading leaving each corner
+    Loop(Vec<(Dir, (usize, usize))>),
+}
 
-        for pos in self.visited.iter() {
-            count.insert(pos.1);
+#[derive(Debug, Clone, Copy)]
+pub struct Guard {
+    position: (usize, usize),
+    direction: Dir,
+}
+
+impl Guard {
+    pub fn new(position: (usize, usize), direction: Dir) -> Self {
+        Self { position, direction }
+    }
+
+    // Every cell walked until the guard leaves, with the heading on leaving that cell
+    pub fn route(&self, table: &JumpTable) -> Vec<(Dir, (usize, usize))> {
+        let (mut pos, mut dir) = (self.position, self.direction);
+        let mut route = vec![(dir, pos)];
+
+        loop {
+            let stop = table.next_stop(pos, dir);
+            let end = stop.unwrap_or_else(|| table.edge(pos, dir));
+            while pos != end {
+                pos = match dir {
+                    Dir::North => (pos.0, pos.1 - 1),
+                    Dir::East  => (pos.0 + 1, pos.1),
+                    Dir::South => (pos.0, pos.1 + 1),
+                    Dir::West  => (pos.0 - 1, pos.1),
+                };
+                route.push((dir, pos));
+            }
+            if stop.is_none() {
+                return route;
+            }
+            dir.right();
+            route.last_mut().unwrap().0 = dir;
+        }
+    }
+
+    // Jumps from obstacle to obstacle until the guard either leaves or repeats a corner
+    pub fn patrol(&self, table: &JumpTable) -> Patrol {
+        let (mut pos, mut dir) = (self.position, self.direction);
+        let mut corners = Vec::new();
+        let mut seen = HashMap::new();
+
+        while let Some(stop) = table.next_stop(pos, dir) {
+            pos = stop;
+            dir.right();
+            if let Some(&idx) = seen.get(&(dir, pos)) {
+                return Patrol::Loop(corners.split_off(idx));
+            }
+            seen.insert((dir, pos), corners.len());
+            corners.push((dir, pos));
         }
 
-        count.len()
+        Patrol::Exit
     }
 }
 
@@ -156,17 +203,14 @@ pub fn solve_part1(
         (usize, usize),
     ),
 ) -> usize {
-    let mut guard = Guard::new(start.clone(), bounds.clone());
+    let table = JumpTable::new(obstacles, *bounds);
+    let guard = Guard::new(*start, Dir::North);
 
-    loop {
-        match guard.look(obstacles) {
-            Ok(_) => guard.step(),
-            Err(MapError::Obstacle) => guard.direction.right(),
-            Err(MapError::Bounds) => break,
-        }
-    }
-
-    guard.visited()
+    guard.route(&table)
+        .into_iter()
+        .map(|(_, pos)| pos)
+        .collect::<HashSet<_>>()
+        .len()
 }
 
 #[aoc(day6, part2)]
@@ -177,26 +221,26 @@ pub fn solve_part2(
         (usize, usize),
     ),
 ) -> usize {
-    let mut guard = Guard::new(start.clone(), bounds.clone());
-    let mut obstructions = 0;
-
-    loop {
-        match guard.look(obstacles) {
-            Ok(new_pos) => {
-                    let mut new_obs = obstacles.clone();
-                    
-                    new_obs.insert(new_pos);
-                    if guard.test_loop(&new_obs) {
-                        obstructions += 1;
-                    }
-                    guard.step();
-                },
-            Err(MapError::Obstacle) => guard.direction.right(),
-            Err(MapError::Bounds) => break,
-        }
-    }
+    let table = JumpTable::new(obstacles, *bounds);
+    let route = Guard::new(*start, Dir::North).route(&table);
+
+    // Only the first visit to a cell can be obstructed, and the guard can resume from the
+    // state just before it, as nothing earlier on the route changes
+    let mut seen = HashSet::from([*start]);
+    let candidates: Vec<_> = route.windows(2)
+        .filter(|pair| seen.insert(pair[1].1))
+        .map(|pair| (pair[1].1, Guard::new(pair[0].1, pair[0].0)))
+        .collect();
 
-    obstructions
+    candidates.par_iter()
+        .map_init(|| table.clone(), |table, &(obstruction, scout)| {
+            table.insert(obstruction);
+            let patrol = scout.patrol(table);
+            table.remove(obstruction);
+            patrol != Patrol::Exit
+        })
+        .filter(|&looped| looped)
+        .count()
 }
 
 #[cfg(test)]
@@ -223,4 +267,34 @@ mod tests {
     fn part2_test() {
         assert_eq!(solve_part2(&input_generator(TEST)), 6);
     }
+
+    #[test]
+    fn jump_table_test() {
+        let (obstacles, start, bounds) = input_generator(TEST);
+        let mut table = JumpTable::new(&obstacles, bounds);
+        assert_eq!(table.next_stop(start, Dir::North), Some((4, 1)));
+        assert_eq!(table.next_stop(start, Dir::West), Some((2, 6)));
+        assert_eq!(table.next_stop(start, Dir::South), None);
+        assert!(table.insert((4, 8)));
+        assert_eq!(table.next_stop(start, Dir::South), Some((4, 7)));
+        assert!(table.remove((4, 8)));
+        assert!(!table.contains((4, 8)));
+    }
+
+    #[test]
+    fn patrol_cycle_test() {
+        let (obstacles, start, bounds) = input_generator(TEST);
+        let mut table = JumpTable::new(&obstacles, bounds);
+        assert_eq!(Guard::new(start, Dir::North).patrol(&table), Patrol::Exit);
+        table.insert((3, 6));
+        assert_eq!(
+            Guard::new(start, Dir::North).patrol(&table),
+            Patrol::Loop(vec![
+                (Dir::East, (4, 1)),
+                (Dir::South, (8, 1)),
+                (Dir::West, (8, 6)),
+                (Dir::North, (4, 6)),
+            ])
+        );
+    }
 }
\ No newline at end of file
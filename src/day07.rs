@@ -11,51 +11,232 @@ pub fn input_generator(input: &str) -> Vec<(usize, Vec<usize>)> {
         }).collect()
 }
 
-fn validator(nums: &Vec<usize>, idx: usize, current: usize, test: &usize, part2: bool) -> bool {
-    if idx >= nums.len() {
-        return current == *test;
+// A binary operator that can be undone, so equations can be searched backwards from the
+// test value. `invert` should return None whenever no left operand could give `result`,
+// which is what prunes the search. When every left operand gives `result`, `absorbs` says so
+// and whatever comes before it only has to evaluate at all.
+pub trait Operator: Sync {
+    fn symbol(&self) -> String;
+    fn apply(&self, lhs: usize, rhs: usize) -> Option<usize>;
+    fn invert(&self, result: usize, rhs: usize) -> Option<usize>;
+
+    fn absorbs(&self, _result: usize, _rhs: usize) -> bool {
+        false
     }
+}
+
+pub struct Add;
+pub struct Mul;
+pub struct Sub;
+pub struct Xor;
+pub struct Concat {
+    base: usize,
+}
+
+impl Operator for Add {
+    fn symbol(&self) -> String { "+".to_string() }
 
-    if validator(nums, idx + 1, current + nums[idx], test, part2) {
-        return true;
+    fn apply(&self, lhs: usize, rhs: usize) -> Option<usize> {
+        lhs.checked_add(rhs)
     }
-    if validator(nums, idx + 1, current * nums[idx], test, part2) {
-        return true;
+
+    fn invert(&self, result: usize, rhs: usize) -> Option<usize> {
+        result.checked_sub(rhs)
     }
-    if part2 {
-        let combined = current * 10usize.pow((nums[idx] as f64).log10() as u32 + 1) + nums[idx];
-        if validator(nums, idx + 1, combined, test, part2) {
-            return true;
+}
+
+impl Operator for Mul {
+    fn symbol(&self) -> String { "*".to_string() }
+
+    fn apply(&self, lhs: usize, rhs: usize) -> Option<usize> {
+        lhs.checked_mul(rhs)
+    }
+
+    // Anything times zero is zero, so 0 is as good a left operand as any
+    fn invert(&self, result: usize, rhs: usize) -> Option<usize> {
+        match rhs {
+            0 if result == 0 => Some(0),
+            0 => None,
+            _ if result % rhs == 0 => Some(result / rhs),
+            _ => None,
         }
     }
 
-    false
+    fn absorbs(&self, result: usize, rhs: usize) -> bool {
+        result == 0 && rhs == 0
+    }
 }
 
-#[aoc(day7, part1)]
-pub fn solve_part1(input: &Vec<(usize, Vec<usize>)>) -> usize {
-    let mut result = 0;
+impl Operator for Sub {
+    fn symbol(&self) -> String { "-".to_string() }
+
+    fn apply(&self, lhs: usize, rhs: usize) -> Option<usize> {
+        lhs.checked_sub(rhs)
+    }
 
-    for (test, nums) in input.iter() {
-        if validator(nums, 1, nums[0], test, false) {
-            result += test;
+    fn invert(&self, result: usize, rhs: usize) -> Option<usize> {
+        result.checked_add(rhs)
+    }
+}
+
+impl Operator for Xor {
+    fn symbol(&self) -> String { "^".to_string() }
+
+    fn apply(&self, lhs: usize, rhs: usize) -> Option<usize> {
+        Some(lhs ^ rhs)
+    }
+
+    fn invert(&self, result: usize, rhs: usize) -> Option<usize> {
+        Some(result ^ rhs)
+    }
+}
+
+impl Concat {
+    // Bases below 2 don't have digits to append
+    pub fn new(base: usize) -> Self {
+        assert!(base >= 2, "concat needs a base of at least 2, got {}", base);
+        Self { base }
+    }
+
+    // Smallest power of the base above n, i.e. the shift needed to append n, or None if that
+    // doesn't fit
+    fn shift(&self, n: usize) -> Option<usize> {
+        let mut shift = self.base;
+        while shift <= n {
+            shift = shift.checked_mul(self.base)?;
         }
+        Some(shift)
+    }
+}
+
+impl Operator for Concat {
+    fn symbol(&self) -> String {
+        if self.base == 10 { "||".to_string() } else { format!("||{}", self.base) }
+    }
+
+    fn apply(&self, lhs: usize, rhs: usize) -> Option<usize> {
+        lhs.checked_mul(self.shift(rhs)?)?.checked_add(rhs)
     }
 
-    result
+    // The result has to end with the digits of rhs
+    fn invert(&self, result: usize, rhs: usize) -> Option<usize> {
+        let shift = self.shift(rhs)?;
+        if result % shift == rhs { Some(result / shift) } else { None }
+    }
 }
 
-#[aoc(day7, part2)]
-pub fn solve_part2(input: &Vec<(usize, Vec<usize>)>) -> usize {
-    let mut result = 0;
+pub struct Calibrator {
+    ops: Vec<Box<dyn Operator>>,
+}
+
+impl Calibrator {
+    pub fn new() -> Self {
+        Self { ops: Vec::new() }
+    }
+
+    pub fn register(mut self, op: impl Operator + 'static) -> Self {
+        self.ops.push(Box::new(op));
+        self
+    }
+
+    // Operators placed between the numbers, left to right, or None if nothing works
+    pub fn solve(&self, test: usize, nums: &[usize]) -> Option<Vec<&dyn Operator>> {
+        let (&first, rest) = nums.split_first()?;
+        let mut chosen = Vec::with_capacity(rest.len());
+
+        if self.search(test, first, rest, &mut chosen) {
+            chosen.reverse();
+            Some(chosen)
+        } else {
+            None
+        }
+    }
+
+    // Peels the last number off the target until only the first is left
+    fn search<'a>(&'a self, target: usize, first: usize, rest: &[usize], chosen: &mut Vec<&'a dyn Operator>) -> bool {
+        let Some((&last, rest)) = rest.split_last() else {
+            return target == first;
+        };
 
-    for (test, nums) in input.iter() {
-        if validator(nums, 1, nums[0], test, true) {
-            result += test;
+        for op in self.ops.iter() {
+            if op.absorbs(target, last) {
+                // The rest are found forwards, so they get flipped to match everything else
+                chosen.push(op.as_ref());
+                let mark = chosen.len();
+                if self.search_any(first, rest, chosen) {
+                    chosen[mark..].reverse();
+                    return true;
+                }
+                chosen.pop();
+            } else if let Some(prev) = op.invert(target, last) {
+                chosen.push(op.as_ref());
+                if self.search(prev, first, rest, chosen) {
+                    return true;
+                }
+                chosen.pop();
+            }
         }
+
+        false
     }
 
-    result
+    // Any operators at all that get through the numbers without failing, searched forwards
+    fn search_any<'a>(&'a self, acc: usize, rest: &[usize], chosen: &mut Vec<&'a dyn Operator>) -> bool {
+        let Some((&next, rest)) = rest.split_first() else {
+            return true;
+        };
+
+        for op in self.ops.iter() {
+            if let Some(acc) = op.apply(acc, next) {
+                chosen.push(op.as_ref());
+                if self.search_any(acc, rest, chosen) {
+                    return true;
+                }
+                chosen.pop();
+            }
+        }
+
+        false
+    }
+}
+
+impl Default for Calibrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Evaluates strictly left to right, as the elephants intended
+pub fn evaluate(nums: &[usize], ops: &[&dyn Operator]) -> Option<usize> {
+    let (&first, rest) = nums.split_first()?;
+    rest.iter()
+        .zip(ops)
+        .try_fold(first, |acc, (&num, op)| op.apply(acc, num))
+}
+
+pub fn expression(nums: &[usize], ops: &[&dyn Operator]) -> String {
+    let mut expr = nums[0].to_string();
+    for (num, op) in nums[1..].iter().zip(ops) {
+        expr.push_str(&format!(" {} {}", op.symbol(), num));
+    }
+    expr
+}
+
+fn calibration_total(input: &[(usize, Vec<usize>)], calibrator: &Calibrator) -> usize {
+    input.iter()
+        .filter(|(test, nums)| calibrator.solve(*test, nums).is_some())
+        .map(|(test, _)| test)
+        .sum()
+}
+
+#[aoc(day7, part1)]
+pub fn solve_part1(input: &[(usize, Vec<usize>)]) -> usize {
+    calibration_total(input, &Calibrator::new().register(Add).register(Mul))
+}
+
+#[aoc(day7, part2)]
+pub fn solve_part2(input: &[(usize, Vec<usize>)]) -> usize {
+    calibration_total(input, &Calibrator::new().register(Add).register(Mul).register(Concat::new(10)))
 }
 
 #[cfg(test)]
@@ -81,4 +262,62 @@ mod tests {
     fn part2_test() {
         assert_eq!(solve_part2(&input_generator(TEST)), 11387);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn witness_test() {
+        let calibrator = Calibrator::new().register(Add).register(Mul).register(Concat::new(10));
+        let nums = [6, 8, 6, 15];
+        let ops = calibrator.solve(7290, &nums).unwrap();
+        assert_eq!(expression(&nums, &ops), "6 * 8 || 6 * 15");
+        assert_eq!(evaluate(&nums, &ops), Some(7290));
+        assert!(calibrator.solve(83, &[17, 5]).is_none());
+    }
+
+    #[test]
+    fn extra_operators_test() {
+        let calibrator = Calibrator::new().register(Sub).register(Xor).register(Concat::new(2));
+        let nums = [12, 5, 3];
+        let ops = calibrator.solve(31, &nums).unwrap();
+        assert_eq!(evaluate(&nums, &ops), Some(31));
+        assert_eq!(expression(&nums, &ops), "12 - 5 ||2 3");
+    }
+
+    #[test]
+    fn zero_operand_test() {
+        let calibrator = Calibrator::new().register(Add).register(Mul).register(Concat::new(10));
+        for (test, nums) in [(0, vec![5, 0]), (0, vec![6, 8, 11, 6, 0]), (77, vec![14, 0, 7, 7]), (0, vec![0])] {
+            let ops = calibrator.solve(test, &nums).unwrap();
+            assert_eq!(evaluate(&nums, &ops), Some(test));
+        }
+        assert!(calibrator.solve(3, &[5, 0]).is_none());
+
+        // Against trying every combination forwards
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(7);
+        let ops: [&dyn Operator; 3] = [&Add, &Mul, &Concat::new(10)];
+        for _ in 0..2000 {
+            let nums: Vec<usize> = (0..rng.gen_range(1..6)).map(|_| rng.gen_range(0..15)).collect();
+            let test = rng.gen_range(0..200);
+            let brute = (0..3usize.pow(nums.len() as u32 - 1)).any(|mut pick| {
+                let chosen: Vec<_> = (1..nums.len()).map(|_| { let op = ops[pick % 3]; pick /= 3; op }).collect();
+                evaluate(&nums, &chosen) == Some(test)
+            });
+            let solved = calibrator.solve(test, &nums);
+            assert_eq!(solved.is_some(), brute, "{}: {:?}", test, nums);
+            if let Some(chosen) = solved {
+                assert_eq!(evaluate(&nums, &chosen), Some(test));
+            }
+        }
+    }
+
+    #[test]
+    fn concat_test() {
+        assert_eq!(Concat::new(10).apply(12, 345), Some(12345));
+        assert_eq!(Concat::new(10).invert(12345, 345), Some(12));
+        assert_eq!(Concat::new(10).apply(1, usize::MAX), None);
+        assert_eq!(Concat::new(10).invert(usize::MAX, usize::MAX / 2), None);
+        assert_eq!(Concat::new(16).apply(0xab, 0xcd), Some(0xabcd));
+        assert!(std::panic::catch_unwind(|| Concat::new(1)).is_err());
+        assert!(std::panic::catch_unwind(|| Concat::new(0)).is_err());
+    }
+}
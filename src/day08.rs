@@ -1,55 +1,119 @@
 use itertools::Itertools;
+use num::integer::gcd;
 use std::collections::{HashMap, HashSet};
 
-fn valid_antinodes(a: &(isize, isize), b: &(isize, isize), bounds: &(isize, isize)) -> Vec<(isize, isize)>{
-    let mut antinodes = Vec::new();
-
-    let dx = b.0 - a.0;
-    let dy = b.1 - a.1;
-
-    let an1 = (a.0 - dx, a.1 - dy);
-    let an2 = (b.0 + dx, b.1 + dy);
-
-    if an1.0 >= 0 && an1.0 < bounds.0 && an1.1 >= 0 && an1.1 < bounds.1 {
-        antinodes.push(an1);
-    }
-    if an2.0 >= 0 && an2.0 < bounds.0 && an2.1 >= 0 && an2.1 < bounds.1 {
-        antinodes.push(an2);
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resonance {
+    // Points in line with both antennae, where one is `far`/`near` times as distant as the
+    // other. Only points outside the pair count, as in the puzzle.
+    Ratio { near: isize, far: isize },
+    // Every lattice point in line with both antennae
+    Harmonic,
+}
 
-    antinodes
+// Antennae on an integer lattice of any dimension, with the map spanning 0..bounds per axis
+pub struct AntennaMap<const N: usize> {
+    pub antennae: HashMap<char, Vec<[isize; N]>>,
+    pub bounds: [isize; N],
 }
 
-fn valid_harmonics(a: &(isize, isize), b: &(isize, isize), bounds: &(isize, isize)) -> Vec<(isize, isize)>{
-    let mut harmonics = Vec::new();
+impl<const N: usize> AntennaMap<N> {
+    pub fn new(antennae: HashMap<char, Vec<[isize; N]>>, bounds: [isize; N]) -> Self {
+        Self { antennae, bounds }
+    }
 
-    let mut x1 = a.0;
-    let mut y1 = a.1;
+    pub fn contains(&self, point: &[isize; N]) -> bool {
+        point.iter().zip(self.bounds.iter()).all(|(&p, &b)| p >= 0 && p < b)
+    }
 
-    let mut x2 = b.0;
-    let mut y2 = b.1;
+    pub fn antinodes(&self, resonance: Resonance) -> HashMap<char, HashSet<[isize; N]>> {
+        self.antennae
+            .iter()
+            .map(|(&freq, pos)| {
+                let antinodes = pos.iter()
+                    .tuple_combinations()
+                    .flat_map(|(a, b)| self.pair_antinodes(a, b, resonance))
+                    .collect();
+                (freq, antinodes)
+            })
+            .collect()
+    }
 
+    fn pair_antinodes(&self, a: &[isize; N], b: &[isize; N], resonance: Resonance) -> Vec<[isize; N]> {
+        // Reduce the delta to the smallest lattice step along the line
+        let mut step = [0; N];
+        for i in 0..N {
+            step[i] = b[i] - a[i];
+        }
+        let factor = step.iter().fold(0, |acc, &d| gcd(acc, d));
+        if factor == 0 {
+            return Vec::new();
+        }
+        step.iter_mut().for_each(|d| *d /= factor);
+
+        let offset = |from: &[isize; N], k: isize| {
+            let mut point = *from;
+            for i in 0..N {
+                point[i] += k * step[i];
+            }
+            point
+        };
+
+        let mut antinodes = Vec::new();
+        match resonance {
+            Resonance::Ratio { near, far } => {
+                let (near, far) = (near.min(far), near.max(far));
+                // The pair is `factor` steps apart, so the antinode sits near*factor/(far-near)
+                // steps beyond either antenna, if that lands on the lattice
+                if near == far || near * factor % (far - near) != 0 {
+                    return antinodes;
+                }
+                let k = near * factor / (far - near);
+                antinodes.extend([offset(a, -k), offset(b, k)]);
+                antinodes.retain(|point| self.contains(point));
+            },
+            Resonance::Harmonic => {
+                let mut k = 0;
+                while self.contains(&offset(a, k)) {
+                    antinodes.push(offset(a, k));
+                    k -= 1;
+                }
+                k = 1;
+                while self.contains(&offset(a, k)) {
+                    antinodes.push(offset(a, k));
+                    k += 1;
+                }
+            },
+        }
 
-    let dx = b.0 - a.0;
-    let dy = b.1 - a.1;
+        antinodes
+    }
+}
 
-    while x1 >= 0 && x1 < bounds.0 && y1 >= 0 && y1 < bounds.1 {
-        harmonics.push((x1, y1));
-        x1 -= dx;
-        y1 -= dy;
+// Antinodes shared by more than one frequency
+pub fn overlaps<const N: usize>(groups: &HashMap<char, HashSet<[isize; N]>>) -> HashMap<[isize; N], Vec<char>> {
+    let mut shared: HashMap<[isize; N], Vec<char>> = HashMap::new();
 
+    for (&freq, points) in groups {
+        for &point in points {
+            shared.entry(point).or_default().push(freq);
+        }
     }
-    while x2 >= 0 && x2 < bounds.0 && y2 >= 0 && y2 < bounds.1 {
-        harmonics.push((x2, y2));
-        x2 += dx;
-        y2 += dy;
-    }
+    shared.retain(|_, freqs| freqs.len() > 1);
+    shared.values_mut().for_each(|freqs| freqs.sort_unstable());
 
-    harmonics
+    shared
+}
+
+fn unique_antinodes<const N: usize>(groups: HashMap<char, HashSet<[isize; N]>>) -> usize {
+    groups.into_values()
+        .flatten()
+        .collect::<HashSet<_>>()
+        .len()
 }
 
 #[aoc_generator(day8)]
-pub fn input_generator(input: &str) -> (HashMap<char, Vec<(isize, isize)>>, (isize, isize)) {
+pub fn input_generator(input: &str) -> AntennaMap<2> {
     let mut antennae = HashMap::new();
     let mut max_row = 0;
     
@@ -66,61 +130,22 @@ pub fn input_generator(input: &str) -> (HashMap<char, Vec<(isize, isize)>>, (isi
                         antennae
                             .entry(ch)
                             .or_insert(Vec::new())
-                            .push((col as isize, row as isize));
+                            .push([col as isize, row as isize]);
                     }
                 })
         });
 
-    (antennae, (input.lines().next().unwrap().len() as isize, max_row))
+    AntennaMap::new(antennae, [input.lines().next().unwrap().len() as isize, max_row])
 }
 
 #[aoc(day8, part1)]
-pub fn solve_part1(
-    (antennae, bounds): &(
-        HashMap<char, Vec<(isize, isize)>>,
-        (isize, isize),
-    ),
-) -> usize {
-    let mut antinodes: HashSet<(isize, isize)> = HashSet::new();
-    
-    for (_, pos) in antennae {
-        let pairs: Vec<((isize, isize), (isize, isize))> = pos.iter()
-            .copied()
-            .combinations(2)
-            .map(|pair| (pair[0], pair[1]))
-            .collect();
-
-        for (a, b) in pairs {
-            antinodes.extend(valid_antinodes(&a, &b, bounds).into_iter());
-        }
-    }
-    
-    antinodes.len()
+pub fn solve_part1(map: &AntennaMap<2>) -> usize {
+    unique_antinodes(map.antinodes(Resonance::Ratio { near: 1, far: 2 }))
 }
 
-
 #[aoc(day8, part2)]
-pub fn solve_part2(
-    (antennae, bounds): &(
-        HashMap<char, Vec<(isize, isize)>>,
-        (isize, isize),
-    ),
-) -> usize {
-    let mut harmonics: HashSet<(isize, isize)> = HashSet::new();
-    
-    for (_, pos) in antennae {
-        let pairs: Vec<((isize, isize), (isize, isize))> = pos.iter()
-            .copied()
-            .combinations(2)
-            .map(|pair| (pair[0], pair[1]))
-            .collect();
-
-        for (a, b) in pairs {
-            harmonics.extend(valid_harmonics(&a, &b, bounds).into_iter());
-        }
-    }
-
-    harmonics.len()
+pub fn solve_part2(map: &AntennaMap<2>) -> usize {
+    unique_antinodes(map.antinodes(Resonance::Harmonic))
 }
 
 #[cfg(test)]
@@ -165,4 +190,29 @@ const MINI: &str = "T.........
     fn part2_test() {
         assert_eq!(solve_part2(&input_generator(TEST)), 34);
     }
+
+    #[test]
+    fn lattice_test() {
+        // The old stepping by the raw delta only found the antennae and (4, 4)
+        let map = AntennaMap::new(HashMap::from([('a', vec![[0, 0], [2, 2]])]), [5, 5]);
+        let harmonics = &map.antinodes(Resonance::Harmonic)[&'a'];
+        assert_eq!(harmonics.len(), 5);
+        assert!(harmonics.contains(&[1, 1]));
+
+        // Antennae 4 steps apart leave a 1:3 antinode 2 steps beyond each
+        let map = AntennaMap::new(HashMap::from([('a', vec![[4, 4], [8, 8]])]), [12, 12]);
+        let antinodes = &map.antinodes(Resonance::Ratio { near: 1, far: 3 })[&'a'];
+        assert_eq!(antinodes, &HashSet::from([[2, 2], [10, 10]]));
+    }
+
+    #[test]
+    fn volume_test() {
+        let map = AntennaMap::new(
+            HashMap::from([('a', vec![[1, 1, 1], [2, 2, 3]]), ('b', vec![[3, 3, 5], [0, 4, 5]])]),
+            [6, 6, 6],
+        );
+        let groups = map.antinodes(Resonance::Ratio { near: 1, far: 2 });
+        assert_eq!(groups[&'a'], HashSet::from([[3, 3, 5]]));
+        assert_eq!(overlaps(&map.antinodes(Resonance::Harmonic)), HashMap::from([([3, 3, 5], vec!['a', 'b'])]));
+    }
 }
\ No newline at end of file
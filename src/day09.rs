@@ -1,136 +1,185 @@
-#[aoc_generator(day9)]
-pub fn input_generator(input: &str) -> Vec<Option<usize>> {
-    let mut map = Vec::new();
-    let mut id = 0;
-    let mut file = true;
+use std::cmp::{Reverse, min};
+use std::collections::BinaryHeap;
 
-    input
-    .chars()
-    .for_each(|ch| {
-        let num = ch.to_digit(10).unwrap();
-        if file {
-            for _ in 0..num {
-                map.push(Some(id));
-            }
-            id += 1;
-            file = false
-        } else {
-            for _ in 0..num {
-                map.push(None);
-            }
-            file = true;
-        }
-    });
-    
-    map
+// A run of `len` blocks belonging to file `id`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub id: usize,
+    pub start: usize,
+    pub len: usize,
 }
 
-#[aoc(day9, part1)]
-pub fn solve_part1(input: &Vec<Option<usize>>) -> usize {
-    let mut map = input.clone();
-    let mut front_idx = 0;
-    let mut back_idx = input.len() - 1;
-    let mut checksum = 0;
-
-    while front_idx < back_idx {
-        while front_idx < back_idx && map[front_idx].is_some() {
-            front_idx += 1;
-        }
-        while front_idx < back_idx && map[back_idx].is_none() {
-            back_idx -= 1;
-        }
-        if front_idx < back_idx {
-            map.swap(front_idx, back_idx);
+impl Span {
+    fn checksum(&self) -> usize {
+        self.id * (self.len * self.start + self.len * self.len.saturating_sub(1) / 2)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    // Move single blocks from the back into the leftmost free space
+    Fragment,
+    // Move whole files into the leftmost gap that fits
+    FirstFit,
+    // Move whole files into the smallest gap that fits, leftmost of equals
+    BestFit,
+    // Move whole files into the rightmost gap that fits, packing against the end of the disk
+    CompactToEnd,
+}
+
+// Free gaps bucketed by length, each bucket a min-heap on start position
+struct FreeIndex {
+    buckets: Vec<BinaryHeap<Reverse<usize>>>,
+}
+
+impl FreeIndex {
+    fn new(gaps: &[(usize, usize)]) -> Self {
+        let longest = gaps.iter().map(|&(_, len)| len).max().unwrap_or(0);
+        let mut index = Self { buckets: vec![BinaryHeap::new(); longest + 1] };
+        for &(start, len) in gaps {
+            index.insert(start, len);
         }
+
+        index
     }
-    
-    for (idx, &num) in map.iter().enumerate() {
-        if let Some(number) = num {
-            checksum += idx * number;
-        } else {
-            break;
+
+    fn insert(&mut self, start: usize, len: usize) {
+        if len > 0 {
+            self.buckets[len].push(Reverse(start));
         }
     }
-    
-    checksum
+
+    // Claims `len` blocks from the chosen gap starting before `limit`, returning the start
+    fn claim(&mut self, len: usize, limit: usize, strategy: Strategy) -> Option<usize> {
+        let mut candidates = self.buckets.iter()
+            .enumerate()
+            .skip(len)
+            .filter_map(|(size, bucket)| bucket.peek().map(|&Reverse(start)| (start, size)))
+            .filter(|&(start, _)| start < limit);
+
+        let (start, size) = match strategy {
+            Strategy::BestFit => candidates.next(),
+            _ => candidates.min(),
+        }?;
+        self.buckets[size].pop();
+        self.insert(start + len, size - len);
+
+        Some(start)
+    }
 }
 
-#[aoc(day9, part2)]
-pub fn solve_part2(input: &Vec<Option<usize>>) -> usize {
-    let mut map = input.clone();
-    let mut block_map = Vec::new();
-    let mut gap_map = Vec::new();
-    let mut checksum = 0;
-
-    let mut block_start = 0;
-    let mut block_size: usize = 0;
-    let mut gap_start = 0;
-    let mut gap_size: usize = 0;
-    let mut current_id: Option<usize> = None;
-
-    for (idx, entry) in map.iter().enumerate() {
-        match entry {
-            Some(id) => {
-                    if let Some(current_id) = current_id {
-                        if *id != current_id {
-                            block_map.push((block_start, block_size));
-                            block_start = idx;
-                            block_size = 0;
-                        }
-                    } else {
-                        if gap_size > 0 && idx > 0 {
-                            gap_map.push((gap_start, gap_size));
-                            block_start = idx;
-                            gap_size = 0;
-                        }
-                    }
-                    current_id = Some(*id);
-                    block_size += 1;
-                },
-            None     => {
-                    if let Some(_) = current_id {
-                        block_map.push((block_start, block_size));
-                        current_id = None;
-                        block_start = idx;
-                        block_size = 0;
-                    }
-
-                    if gap_size == 0 {
-                        gap_start = idx;
-                    }
-                    gap_size += 1;
-                },
+#[derive(Debug, Clone)]
+pub struct Disk {
+    pub files: Vec<Span>,
+    pub gaps: Vec<(usize, usize)>,
+    pub size: usize,
+}
+
+impl Disk {
+    // Resulting layout of files, in no particular order
+    pub fn compact(&self, strategy: Strategy) -> Vec<Span> {
+        match strategy {
+            Strategy::Fragment => self.fragment(),
+            Strategy::FirstFit | Strategy::BestFit => self.relocate(strategy),
+            // Packing to the end is packing to the front of the disk read backwards
+            Strategy::CompactToEnd => self.mirrored()
+                .relocate(Strategy::FirstFit)
+                .into_iter()
+                .map(|span| Span { start: self.size - span.start - span.len, ..span })
+                .collect(),
         }
     }
 
-    if block_size > 0 {
-        block_map.push((block_start, block_size));
-    }
+    fn fragment(&self) -> Vec<Span> {
+        let mut files = self.files.clone();
+        let mut layout = Vec::new();
+        let mut remaining = files.len();
 
-    for &(block_start, block_size) in block_map.iter().rev() {
-        for (gap_idx, &(gap_start, gap_size)) in gap_map.iter().enumerate() {
-            if gap_size >= block_size  && block_start > gap_start {
-                for i in 0..block_size {
-                    map.swap(gap_start + i, block_start + i);
+        'gaps: for &(mut start, mut len) in self.gaps.iter() {
+            while len > 0 && remaining > 0 {
+                let file = &mut files[remaining - 1];
+                if file.start < start {
+                    break 'gaps;
                 }
-                gap_map[gap_idx].0 += block_size;
-                gap_map[gap_idx].1 -= block_size;
-                // This /should/ improve performance
-                if gap_map[gap_idx].1 == 0 {
-                    gap_map.retain(|&(_, size)| size > 0);
+                let moved = min(len, file.len);
+                layout.push(Span { id: file.id, start, len: moved });
+                file.len -= moved;
+                start += moved;
+                len -= moved;
+                if file.len == 0 {
+                    remaining -= 1;
                 }
-                break;
             }
         }
+        layout.extend(files[..remaining].iter().filter(|file| file.len > 0));
+
+        layout
     }
 
-    for (idx, &num) in map.iter().enumerate() {
-        if let Some(number) = num {
-            checksum += idx * number;
+    fn relocate(&self, strategy: Strategy) -> Vec<Span> {
+        let mut index = FreeIndex::new(&self.gaps);
+        let mut layout = self.files.clone();
+
+        // Space freed by a move is always behind the files still to go, so never reused
+        layout.sort_unstable_by_key(|file| Reverse(file.start));
+        for file in layout.iter_mut() {
+            if let Some(start) = index.claim(file.len, file.start, strategy) {
+                file.start = start;
+            }
+        }
+
+        layout
+    }
+
+    fn mirrored(&self) -> Self {
+        let flip = |start: usize, len: usize| self.size - start - len;
+        Self {
+            files: self.files.iter().map(|file| Span { start: flip(file.start, file.len), ..*file }).collect(),
+            gaps: self.gaps.iter().map(|&(start, len)| (flip(start, len), len)).collect(),
+            size: self.size,
         }
     }
+}
+
+pub fn checksum(layout: &[Span]) -> usize {
+    layout.iter().map(Span::checksum).sum()
+}
+
+#[aoc_generator(day9)]
+pub fn input_generator(input: &str) -> Disk {
+    let mut files = Vec::new();
+    let mut gaps = Vec::new();
+    let mut start = 0;
+
+    input
+    .trim()
+    .chars()
+    .enumerate()
+    .for_each(|(idx, ch)| {
+        let len = ch.to_digit(10).unwrap() as usize;
+        if idx % 2 == 0 {
+            files.push(Span { id: idx / 2, start, len });
+        } else if len > 0 {
+            // An empty file between two gaps leaves them as one free run
+            match gaps.last_mut() {
+                Some((gap_start, gap_len)) if *gap_start + *gap_len == start => *gap_len += len,
+                _ => gaps.push((start, len)),
+            }
+        }
+        start += len;
+    });
+    
+    Disk { files, gaps, size: start }
+}
 
-    checksum
+#[aoc(day9, part1)]
+pub fn solve_part1(disk: &Disk) -> usize {
+    checksum(&disk.compact(Strategy::Fragment))
+}
+
+#[aoc(day9, part2)]
+pub fn solve_part2(disk: &Disk) -> usize {
+    checksum(&disk.compact(Strategy::FirstFit))
 }
 
 #[cfg(test)]
@@ -148,4 +197,22 @@ mod tests {
     fn part2_test() {
         assert_eq!(solve_part2(&input_generator(TEST)), 2858);
     }
+
+    #[test]
+    fn strategies_test() {
+        let disk = input_generator("13111");
+        assert_eq!(checksum(&disk.compact(Strategy::FirstFit)), 4);
+        assert_eq!(checksum(&disk.compact(Strategy::BestFit)), 11);
+
+        let disk = input_generator("12345");
+        assert_eq!(checksum(&disk.compact(Strategy::FirstFit)), 132);
+        assert_eq!(checksum(&disk.compact(Strategy::CompactToEnd)), 141);
+    }
+
+    #[test]
+    fn empty_file_test() {
+        let disk = input_generator("74597005034277245");
+        assert!(disk.gaps.contains(&(32, 8)));
+        assert_eq!(solve_part2(&disk), 3202);
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use rayon::prelude::*;
+use std::cmp::Reverse;
 use std::collections::HashSet;
 
 pub struct Map {
@@ -8,18 +8,45 @@ pub struct Map {
     max_row: usize,
 }
 
+const PEAK: usize = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    // Exactly one higher, as the puzzle describes
+    Gradual,
+    // Any higher tile
+    Ascending,
+    // At most this much higher
+    MaxSlope(usize),
+}
+
+impl Step {
+    fn allows(&self, from: usize, to: usize) -> bool {
+        match self {
+            Step::Gradual     => to == from + 1,
+            Step::Ascending   => to > from,
+            Step::MaxSlope(k) => to > from && to - from <= *k,
+        }
+    }
+}
+
 impl Map {
-    fn next_up(&self, pos: &(usize, usize), height: &usize) -> Vec<(usize, (usize, usize))> {
+    fn height(&self, pos: &(usize, usize)) -> usize {
+        self.grid[pos.1][pos.0]
+    }
+
+    fn next_up(&self, pos: &(usize, usize), step: Step) -> Vec<(usize, usize)> {
         let mut next = Vec::new();
-        let directions = vec![(0, 1), (0, -1), (1, 0), (-1, 0)];
+        let directions = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+        let height = self.height(pos);
 
         for (dx, dy) in directions {
             let x = pos.0 as i32 + dx;
             let y = pos.1 as i32 + dy;
             if x >= 0 && x < self.max_col as i32 && y >= 0 && y < self.max_row as i32 {
-                let next_height = self.grid[y as usize][x as usize];
-                if next_height == height + 1 {
-                    next.push((next_height, (x as usize, y as usize)));
+                let next_pos = (x as usize, y as usize);
+                if step.allows(height, self.height(&next_pos)) {
+                    next.push(next_pos);
                 }
             }
         }
@@ -28,6 +55,96 @@ impl Map {
     }
 }
 
+// Reachable peaks and trail ratings for every tile, filled in from the peaks down so each
+// tile only needs the tiles one step above it
+pub struct Trails<'a> {
+    map: &'a Map,
+    step: Step,
+    peaks: Vec<HashSet<(usize, usize)>>,
+    ratings: Vec<usize>,
+}
+
+impl<'a> Trails<'a> {
+    pub fn new(map: &'a Map, step: Step) -> Self {
+        let size = map.max_col * map.max_row;
+        let mut peaks = vec![HashSet::new(); size];
+        let mut ratings = vec![0; size];
+
+        let mut tiles: Vec<(usize, usize)> = (0..map.max_row)
+            .flat_map(|row| (0..map.max_col).map(move |col| (col, row)))
+            .collect();
+        tiles.sort_unstable_by_key(|pos| Reverse(map.height(pos)));
+
+        for pos in tiles {
+            let idx = pos.1 * map.max_col + pos.0;
+            if map.height(&pos) == PEAK {
+                peaks[idx].insert(pos);
+                ratings[idx] = 1;
+                continue;
+            }
+            for next in map.next_up(&pos, step) {
+                let next_idx = next.1 * map.max_col + next.0;
+                let above = peaks[next_idx].clone();
+                peaks[idx].extend(above);
+                ratings[idx] += ratings[next_idx];
+            }
+        }
+
+        Self { map, step, peaks, ratings }
+    }
+
+    fn idx(&self, pos: &(usize, usize)) -> usize {
+        pos.1 * self.map.max_col + pos.0
+    }
+
+    // Number of distinct peaks reachable from a tile
+    pub fn score(&self, pos: &(usize, usize)) -> usize {
+        self.peaks[self.idx(pos)].len()
+    }
+
+    // Number of distinct trails from a tile to any peak
+    pub fn rating(&self, pos: &(usize, usize)) -> usize {
+        self.ratings[self.idx(pos)]
+    }
+
+    pub fn peaks(&self, pos: &(usize, usize)) -> &HashSet<(usize, usize)> {
+        &self.peaks[self.idx(pos)]
+    }
+
+    // Lazily walks every trail from a tile, never straying onto dead ends
+    pub fn paths(&self, pos: &(usize, usize)) -> TrailPaths<'_, 'a> {
+        let stack = if self.rating(pos) > 0 { vec![vec![*pos]] } else { Vec::new() };
+        TrailPaths { trails: self, stack }
+    }
+}
+
+pub struct TrailPaths<'t, 'a> {
+    trails: &'t Trails<'a>,
+    stack: Vec<Vec<(usize, usize)>>,
+}
+
+impl Iterator for TrailPaths<'_, '_> {
+    type Item = Vec<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(path) = self.stack.pop() {
+            let pos = *path.last().unwrap();
+            if self.trails.map.height(&pos) == PEAK {
+                return Some(path);
+            }
+            for next in self.trails.map.next_up(&pos, self.trails.step) {
+                if self.trails.rating(&next) > 0 {
+                    let mut longer = path.clone();
+                    longer.push(next);
+                    self.stack.push(longer);
+                }
+            }
+        }
+
+        None
+    }
+}
+
 #[aoc_generator(day10)]
 pub fn input_generator(input: &str) -> Map {
     let mut heads = Vec::new();
@@ -55,52 +172,14 @@ pub fn input_generator(input: &str) -> Map {
 
 #[aoc(day10, part1)]
 pub fn solve_part1(input: &Map) -> usize {
-    input.heads
-        .par_iter()
-        .map(|&head| {
-            let mut visited: HashSet<(usize, usize)> = HashSet::new();
-            let mut next_pos = vec![head];
-            let mut thread_score = 0;
-
-            while !next_pos.is_empty() {
-                let (height, pos) = next_pos.pop().unwrap();
-
-                if !visited.contains(&pos) {
-                    visited.insert(pos.clone());
-                    if height == 9 {
-                        thread_score += 1;
-                    } else {
-                        next_pos.extend(input.next_up(&pos, &height));
-                    }
-                }
-            }
-
-            thread_score
-        })
-        .sum()
+    let trails = Trails::new(input, Step::Gradual);
+    input.heads.iter().map(|(_, pos)| trails.score(pos)).sum()
 }
 
 #[aoc(day10, part2)]
 pub fn solve_part2(input: &Map) -> usize {
-    input.heads
-        .par_iter()
-        .map(|&head| {
-            let mut next_pos = vec![head];
-            let mut thread_score = 0;
-
-            while !next_pos.is_empty() {
-                let (height, pos) = next_pos.pop().unwrap();
-
-                if height == 9 {
-                    thread_score += 1;
-                } else {
-                    next_pos.extend(input.next_up(&pos, &height));
-                }
-            }
-
-            thread_score
-        })
-        .sum()
+    let trails = Trails::new(input, Step::Gradual);
+    input.heads.iter().map(|(_, pos)| trails.rating(pos)).sum()
 }
 
 #[cfg(test)]
@@ -125,4 +204,25 @@ mod tests {
     fn part2_test() {
         assert_eq!(solve_part2(&input_generator(TEST)), 81);
     }
+
+    #[test]
+    fn paths_test() {
+        let map = input_generator(TEST);
+        let trails = Trails::new(&map, Step::Gradual);
+        let paths: Vec<_> = trails.paths(&(2, 0)).collect();
+        assert_eq!(paths.len(), trails.rating(&(2, 0)));
+        assert!(paths.iter().all(|path| path.len() == 10 && path[0] == (2, 0)));
+        assert_eq!(paths.iter().map(|path| path[9]).collect::<HashSet<_>>(), *trails.peaks(&(2, 0)));
+    }
+
+    #[test]
+    fn step_rules_test() {
+        let map = input_generator("0259
+                                   1369");
+        assert_eq!(Trails::new(&map, Step::Gradual).rating(&(0, 0)), 0);
+        assert_eq!(Trails::new(&map, Step::MaxSlope(2)).rating(&(0, 0)), 0);
+        assert_eq!(Trails::new(&map, Step::MaxSlope(3)).rating(&(0, 0)), 3);
+        assert_eq!(Trails::new(&map, Step::Ascending).rating(&(0, 0)), 4);
+        assert_eq!(Trails::new(&map, Step::Ascending).score(&(0, 0)), 2);
+    }
 }
\ No newline at end of file
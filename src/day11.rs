@@ -1,62 +1,121 @@
+use num::{BigUint, One, ToPrimitive, Zero};
 use std::collections::HashMap;
 
-#[aoc_generator(day11)]
-pub fn input_generator(input: &str) -> Vec<usize> {
-    input.split_whitespace().map(|num| num.parse().unwrap()).collect()
+// How many stones carry each engraving
+pub type Population = HashMap<BigUint, BigUint>;
+
+type Predicate = Box<dyn Fn(&BigUint) -> bool>;
+type Transform = Box<dyn Fn(&BigUint) -> Vec<BigUint>>;
+
+// A stone matching the predicate is replaced by whatever the transform returns
+pub struct Rule {
+    predicate: Predicate,
+    transform: Transform,
 }
 
-#[aoc(day11, part1)]
-pub fn solve_part1(input: &Vec<usize>) -> usize {
-    let mut stones: HashMap<usize, usize> = HashMap::new();
-    input.iter().for_each(|&num| *stones.entry(num).or_insert(0) += 1);
-    
-    for _ in 0..25 {
-        let mut new_map: HashMap<usize, usize> = HashMap::new();
-        for (&key, &val) in stones.iter() {
-            if key == 0 {
-                *new_map.entry(1).or_insert(0) += val;
-            } else {
-                let digit_count = (key as f64).log10() as u32 + 1;
-                if digit_count % 2 == 0 {
-                    let divisor = 10_usize.pow(digit_count / 2);
-                    *new_map.entry(key / divisor).or_insert(0) += val;
-                    *new_map.entry(key % divisor).or_insert(0) += val;
-                } else {
-                    *new_map.entry(key * 2024).or_insert(0) += val;
-                }
-            }
-        }
-        stones = new_map;
+impl Rule {
+    pub fn new(
+        predicate: impl Fn(&BigUint) -> bool + 'static,
+        transform: impl Fn(&BigUint) -> Vec<BigUint> + 'static,
+    ) -> Self {
+        Self { predicate: Box::new(predicate), transform: Box::new(transform) }
     }
+}
 
-    stones.iter().map(|(_, val)| val).sum()
+pub fn digit_count(num: &BigUint) -> u32 {
+    num.to_str_radix(10).len() as u32
 }
 
-#[aoc(day11, part2)]
-pub fn solve_part2(input: &Vec<usize>) -> usize {
-    let mut stones: HashMap<usize, usize> = HashMap::new();
-    input.iter().for_each(|&num| *stones.entry(num).or_insert(0) += 1);
-    
-    for _ in 0..75 {
-        let mut new_map: HashMap<usize, usize> = HashMap::new();
-        for (&key, &val) in stones.iter() {
-            if key == 0 {
-                *new_map.entry(1).or_insert(0) += val;
-            } else {
-                let digit_count = (key as f64).log10() as u32 + 1;
-                if digit_count % 2 == 0 {
-                    let divisor = 10_usize.pow(digit_count / 2);
-                    *new_map.entry(key / divisor).or_insert(0) += val;
-                    *new_map.entry(key % divisor).or_insert(0) += val;
-                } else {
-                    *new_map.entry(key * 2024).or_insert(0) += val;
-                }
+pub struct StoneEngine {
+    rules: Vec<Rule>,
+}
+
+impl StoneEngine {
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    // Rules are tried in the order registered, and the first match wins
+    pub fn register(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    // The rules engraved on the Plutonian pebbles
+    pub fn plutonian() -> Self {
+        Self::new()
+            .register(Rule::new(|num| num.is_zero(), |_| vec![BigUint::one()]))
+            .register(Rule::new(
+                |num| digit_count(num) % 2 == 0,
+                |num| {
+                    let divisor = BigUint::from(10u32).pow(digit_count(num) / 2);
+                    vec![num / &divisor, num % &divisor]
+                },
+            ))
+            .register(Rule::new(|_| true, |num| vec![num * 2024u32]))
+    }
+
+    pub fn blink(&self, stones: &Population) -> Population {
+        let mut next = Population::new();
+
+        for (num, count) in stones {
+            // A stone no rule applies to stays as it is
+            let changed = match self.rules.iter().find(|rule| (rule.predicate)(num)) {
+                Some(rule) => (rule.transform)(num),
+                None => vec![num.clone()],
+            };
+            for new in changed {
+                *next.entry(new).or_default() += count;
             }
         }
-        stones = new_map;
+
+        next
+    }
+
+    pub fn run(&self, stones: &[BigUint], blinks: usize) -> Population {
+        (0..blinks).fold(populate(stones), |pop, _| self.blink(&pop))
     }
 
-    stones.iter().map(|(_, val)| val).sum()
+    // The population before any blinks, then after each one
+    pub fn history(&self, stones: &[BigUint], blinks: usize) -> Vec<Population> {
+        let mut history = vec![populate(stones)];
+        for _ in 0..blinks {
+            history.push(self.blink(history.last().unwrap()));
+        }
+
+        history
+    }
+}
+
+impl Default for StoneEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn populate(stones: &[BigUint]) -> Population {
+    let mut pop = Population::new();
+    stones.iter().for_each(|num| *pop.entry(num.clone()).or_default() += 1u32);
+    pop
+}
+
+pub fn total(pop: &Population) -> BigUint {
+    pop.values().sum()
+}
+
+#[aoc_generator(day11)]
+pub fn input_generator(input: &str) -> Vec<BigUint> {
+    input.split_whitespace().map(|num| num.parse().unwrap()).collect()
+}
+
+#[aoc(day11, part1)]
+pub fn solve_part1(input: &[BigUint]) -> usize {
+    total(&StoneEngine::plutonian().run(input, 25)).to_usize().unwrap()
+}
+
+#[aoc(day11, part2)]
+pub fn solve_part2(input: &[BigUint]) -> usize {
+    total(&StoneEngine::plutonian().run(input, 75)).to_usize().unwrap()
 }
 
 #[cfg(test)]
@@ -69,4 +128,23 @@ mod tests {
     fn part1_test() {
         assert_eq!(solve_part1(&input_generator(TEST)), 55312);
     }
+
+    #[test]
+    fn history_test() {
+        let history = StoneEngine::plutonian().history(&input_generator(TEST), 6);
+        let sizes: Vec<_> = history.iter().map(|pop| total(pop).to_usize().unwrap()).collect();
+        assert_eq!(sizes, vec![2, 3, 4, 5, 9, 13, 22]);
+        assert_eq!(history[6][&BigUint::from(2u32)], BigUint::from(4u32));
+    }
+
+    #[test]
+    fn big_stones_test() {
+        // Would overflow a usize by the second blink
+        let engine = StoneEngine::new()
+            .register(Rule::new(|num| num.is_zero(), |_| vec![BigUint::one()]))
+            .register(Rule::new(|_| true, |num| vec![num * 10_000_000_000u64, num.clone()]));
+        let pop = engine.run(&input_generator("1 0"), 2);
+        assert_eq!(total(&pop), BigUint::from(6u32));
+        assert!(pop.contains_key(&BigUint::from(10u32).pow(20)));
+    }
 }
\ No newline at end of file
@@ -1,181 +1,22 @@
-use std::collections::{HashSet, VecDeque};
-
-pub struct Map {
-    grid: Vec<Vec<char>>,
-    rows: usize,
-    cols: usize,
-}
-
-// Voronoi cells
-#[derive(Debug)]
-pub struct Region {
-    plant_type: char,
-    coords: HashSet<(usize, usize)>,
-    area: usize,
-}
-
-fn voronoi_tesselation(grid: &Vec<Vec<char>>, rows: usize, cols: usize) -> Vec<Region> {
-    let mut visited = HashSet::new();
-    let mut regions = Vec::new();
-
-    let directions = [(0, 1), (1, 0), (0, -1), (-1, 0)];
-
-    // I'll keep things row-major for consistency
-    for row in 0..rows {
-        for col in 0..cols {
-            if visited.contains(&(row, col)) {
-                continue;
-            }
-
-            let plant_type = grid[row][col];
-            let mut coords = HashSet::new();
-            let mut queue = VecDeque::new();
-            queue.push_back((row, col));
-
-            // Find all coordinates in the region, by region
-            while let Some((r, c)) = queue.pop_front() {
-                if visited.contains(&(r, c)) {
-                    continue;
-                }
-
-                visited.insert((r, c));
-                coords.insert((r, c));
-
-                for (dr, dc) in &directions {
-                    let new_r = (r as i32 + dr) as usize;
-                    let new_c = (c as i32 + dc) as usize;
-
-                    if new_r < rows && new_c < cols &&
-                       grid[new_r][new_c] == plant_type &&
-                       !visited.contains(&(new_r, new_c)) {
-                        queue.push_back((new_r, new_c));
-                       }
-                }
-            }
-
-            regions.push(Region {
-                plant_type,
-                area: coords.len(),
-                coords,
-            });
-        }
-    }
-
-    regions
-}
+use crate::prelude::*;
 
 #[aoc_generator(day12)]
-pub fn input_generator(input: &str) -> (Vec<Region>, Map) {
-    let max_col = input.lines().next().unwrap().len();
-    let mut max_row = 0;
-
-    let grid: Vec<Vec<char>> = input
-        .lines()
-        .map(|line| {
-            max_row += 1;
-            line.chars()
-                .collect()
-        }).collect();
+pub fn input_generator(input: &str) -> Vec<Region<char>> {
+    let width = input.lines().next().unwrap().len();
+    let entity: Vec<char> = input.lines().flat_map(|line| line.chars()).collect();
+    let height = entity.len() / width;
 
-    (
-        voronoi_tesselation(&grid, max_row, max_col),
-        Map {
-            grid,
-            rows: max_row,
-            cols: max_col,
-        },
-    )
+    Grid::new(width, height, entity).regions()
 }
 
 #[aoc(day12, part1)]
-pub fn solve_part1((regions, map): &(Vec<Region>, Map)) -> usize {
-    let mut total = 0;
-
-    let directions = [(0, 1), (1, 0), (0, -1), (-1, 0)];
-
-    for region in regions {
-        let mut perimeter = 0;
-        for &(r, c) in &region.coords {
-            for (dr, dc) in &directions {
-                let new_r = (r as i32 + dr) as usize;
-                let new_c = (c as i32 + dc) as usize;
-
-                if new_r >= map.rows || new_c >= map.cols ||
-                    map.grid[new_r][new_c] != region.plant_type {
-                    perimeter += 1;
-                    }
-            }
-        }
-
-        total += region.coords.len() * perimeter;
-    }
-
-    total
+pub fn solve_part1(regions: &[Region<char>]) -> usize {
+    regions.iter().map(|region| region.area * region.perimeter).sum()
 }
 
 #[aoc(day12, part2)]
-pub fn solve_part2((regions, map): &(Vec<Region>, Map)) -> usize {
-    let mut total = 0;
-
-    let directions = [(0, 1), (1, 0), (0, -1), (-1, 0)];
-
-    for region in regions {
-        let mut edges = HashSet::new();
-        for &(r, c) in &region.coords {
-            for (idx, (dr, dc)) in directions.iter().enumerate() {
-                let new_r = (r as i32 + dr) as usize;
-                let new_c = (c as i32 + dc) as usize;
-
-                if new_r >= map.rows || new_c >= map.cols ||
-                   map.grid[new_r][new_c] != region.plant_type {
-                    edges.insert((r, c, idx));
-                }
-            }
-        }
-
-        let mut sides = 0;
-        let mut visited_edges = HashSet::new();
-
-        for &(r, c, dir) in &edges {
-            if visited_edges.contains(&(r, c, dir)) {
-                continue;
-            }
-
-            sides += 1;
-
-            let perp_dirs = match dir {
-                0 | 2 => [(1, 0), (-1, 0)],
-                1 | 3 => [(0, 1), (0, -1)],
-                _ => unreachable!(),
-            };
-
-            for &(dr, dc) in &perp_dirs {
-                let mut curr_r = r as i32;
-                let mut curr_c = c as i32;
-
-                loop {
-                    curr_r += dr;
-                    curr_c += dc;
-
-                    if curr_r < 0 || curr_r >= map.rows as i32 ||
-                       curr_c < 0 || curr_c >= map.cols as i32 {
-                        break;
-                    }
-
-                    let curr_coord = (curr_r as usize, curr_c as usize, dir);
-                    if !edges.contains(&curr_coord) {
-                        break;
-                    }
-
-                    visited_edges.insert(curr_coord);
-                }
-            }
-        }
-        
-        total += region.area * sides;
-    }
-
-    total
+pub fn solve_part2(regions: &[Region<char>]) -> usize {
+    regions.iter().map(|region| region.area * region.sides).sum()
 }
 
 #[cfg(test)]
@@ -218,6 +59,12 @@ ABBAAA
 ABBAAA
 AAAAAA";
 
+    const NESTED: &str = "AAAAA
+ABBBA
+ABCBA
+ABBBA
+AAAAA";
+
     #[test]
     fn part1_test1() {
         assert_eq!(solve_part1(&input_generator(TEST1)), 140);
@@ -257,4 +104,28 @@ AAAAAA";
     fn part2_test5() {
         assert_eq!(solve_part2(&input_generator(TEST5)), 368);
     }
+
+    #[test]
+    fn holes_test() {
+        let regions = input_generator(TEST2);
+        assert_eq!(regions[0].holes, 4);
+        assert_eq!(regions[0].bounds, ((0, 0), (4, 4)));
+
+        // The two B blocks only touch at a corner, so make a single hole
+        let regions = input_generator(TEST5);
+        assert_eq!((regions[0].label, regions[0].holes), ('A', 1));
+    }
+
+    #[test]
+    fn nested_test() {
+        let regions = input_generator(NESTED);
+        let summary: Vec<_> = regions.iter()
+            .map(|r| (r.label, r.area, r.perimeter, r.sides, r.holes, r.bounds))
+            .collect();
+        assert_eq!(summary, vec![
+            ('A', 16, 32, 8, 1, ((0, 0), (4, 4))),
+            ('B', 8, 16, 8, 1, ((1, 1), (3, 3))),
+            ('C', 1, 4, 4, 0, ((2, 2), (2, 2))),
+        ]);
+    }
 }
\ No newline at end of file
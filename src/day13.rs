@@ -1,3 +1,7 @@
+use num::integer::{ExtendedGcd, Integer};
+use num::Rational64;
+use std::cmp::Ordering;
+
 #[derive(Debug)]
 pub struct Crane {
     a: (isize, isize),
@@ -14,33 +18,170 @@ impl Crane {
         }
     }
 
-    fn solve(&self, offset: isize) -> Option<(isize, isize)> {
-        let pxo = self.p.0 + offset;
-        let pyo = self.p.1 + offset;
-        
-        let denominator = self.b.1 * self.a.0 - self.b.0 * self.a.1;
-        if denominator == 0 {
-            return None;
-        }
-        
-        let n = (pyo * self.a.0 - pxo * self.a.1) / denominator;
-        
-        let m = if self.a.0 != 0 {
-            (pxo - n * self.b.0) / self.a.0
-        } else if self.a.1 != 0 {
-            (pyo - n * self.b.1) / self.a.1
+    // Cheapest presses of (A, B) that land on the prize, if any
+    pub fn solve(&self, offset: isize, pricing: &Pricing) -> Option<(isize, isize)> {
+        let prize = (self.p.0 + offset, self.p.1 + offset);
+        let denominator = self.a.0 * self.b.1 - self.a.1 * self.b.0;
+
+        let presses = if denominator != 0 {
+            // Independent buttons only ever have the one solution, so check it's whole
+            let m = Rational64::new((prize.0 * self.b.1 - prize.1 * self.b.0) as i64, denominator as i64);
+            let n = Rational64::new((self.a.0 * prize.1 - self.a.1 * prize.0) as i64, denominator as i64);
+            if !m.is_integer() || !n.is_integer() {
+                return None;
+            }
+            (m.to_integer() as isize, n.to_integer() as isize)
         } else {
-            return None;
+            self.solve_collinear(prize, pricing)?
         };
-        
-        if m >= 0 && n >= 0 &&
-           m * self.a.0 + n * self.b.0 == pxo &&
-           m * self.a.1 + n * self.b.1 == pyo {
-            Some((m, n))
+
+        let within = |presses: isize| presses >= 0 && pricing.max_presses.is_none_or(|max| presses <= max);
+        if within(presses.0) && within(presses.1) &&
+           presses.0 * self.a.0 + presses.1 * self.b.0 == prize.0 &&
+           presses.0 * self.a.1 + presses.1 * self.b.1 == prize.1 {
+            Some(presses)
         } else {
             None
         }
     }
+
+    // Both buttons move along the same line, so the prize has to be on it too, and then it's
+    // a single Diophantine equation m*x + n*y = t along whichever axis the line isn't flat in
+    fn solve_collinear(&self, prize: (isize, isize), pricing: &Pricing) -> Option<(isize, isize)> {
+        let (x, y, t) = if self.a.0 != 0 || self.b.0 != 0 {
+            (self.a.0, self.b.0, prize.0)
+        } else if self.a.1 != 0 || self.b.1 != 0 {
+            (self.a.1, self.b.1, prize.1)
+        } else {
+            // Neither button does anything
+            return Some((0, 0));
+        };
+        let line = if self.a != (0, 0) { self.a } else { self.b };
+        if line.0 * prize.1 != line.1 * prize.0 {
+            return None;
+        }
+
+        let ExtendedGcd { gcd, x: s, y: u } = x.extended_gcd(&y);
+        if t % gcd != 0 {
+            return None;
+        }
+        // Every solution is (m0 + k*dm, n0 + k*dn) for integer k
+        let (m0, n0) = (s * (t / gcd), u * (t / gcd));
+        let (dm, dn) = (y / gcd, -x / gcd);
+
+        let mut range = (None, None);
+        if !restrict(m0, dm, pricing.max_presses, &mut range) ||
+           !restrict(n0, dn, pricing.max_presses, &mut range) {
+            return None;
+        }
+
+        if let (Some(lo), Some(hi)) = range {
+            if lo > hi {
+                return None;
+            }
+        }
+
+        // Cost is linear in k, so the cheapest end of the range wins. It can only be open
+        // at that end if a button has a negative cost.
+        let slope = pricing.a * dm + pricing.b * dn;
+        let k = match (range, slope.cmp(&0)) {
+            ((Some(lo), _), Ordering::Greater) => lo,
+            ((_, Some(hi)), Ordering::Less) => hi,
+            ((Some(lo), _), Ordering::Equal) => lo,
+            ((None, Some(hi)), Ordering::Equal) => hi,
+            _ => return None,
+        };
+
+        Some((m0 + k * dm, n0 + k * dn))
+    }
+
+    pub fn cost(&self, presses: (isize, isize), pricing: &Pricing) -> isize {
+        presses.0 * pricing.a + presses.1 * pricing.b
+    }
+}
+
+#[cfg(feature = "z3")]
+impl Crane {
+    // The same question posed to z3 as an integer optimisation problem, which makes a
+    // handy oracle for `solve`
+    pub fn solve_ilp(&self, offset: isize, pricing: &Pricing) -> Option<(isize, isize)> {
+        use z3::ast::{Ast, Int};
+        use z3::{Config, Context, Optimize, SatResult, Solver};
+
+        let ctx = Context::new(&Config::new());
+        let a = Int::new_const(&ctx, "a");
+        let b = Int::new_const(&ctx, "b");
+
+        let zero = Int::from_i64(&ctx, 0);
+        let mut constraints = vec![a.ge(&zero), b.ge(&zero)];
+        if let Some(max) = pricing.max_presses {
+            let max = Int::from_i64(&ctx, max as i64);
+            constraints.extend([a.le(&max), b.le(&max)]);
+        }
+        let x = &a * self.a.0 as i64 + &b * self.b.0 as i64;
+        let y = &a * self.a.1 as i64 + &b * self.b.1 as i64;
+        constraints.push(x._eq(&Int::from_i64(&ctx, (self.p.0 + offset) as i64)));
+        constraints.push(y._eq(&Int::from_i64(&ctx, (self.p.1 + offset) as i64)));
+
+        // Optimize has been seen to run away with problems its presolve proves infeasible,
+        // so rule those out with a plain solver first
+        let solver = Solver::new(&ctx);
+        constraints.iter().for_each(|constraint| solver.assert(constraint));
+        if solver.check() != SatResult::Sat {
+            return None;
+        }
+
+        let opt = Optimize::new(&ctx);
+        constraints.iter().for_each(|constraint| opt.assert(constraint));
+        opt.minimize(&(&a * pricing.a as i64 + &b * pricing.b as i64));
+
+        match opt.check(&[]) {
+            SatResult::Sat => {
+                let model = opt.get_model()?;
+                Some((model.eval(&a, true)?.as_i64()? as isize, model.eval(&b, true)?.as_i64()? as isize))
+            },
+            _ => None,
+        }
+    }
+}
+
+// Narrows the range of k for which 0 <= base + k*step <= max
+fn restrict(base: isize, step: isize, max: Option<isize>, range: &mut (Option<isize>, Option<isize>)) -> bool {
+    let mut tighten = |lo: Option<isize>, hi: Option<isize>| {
+        range.0 = range.0.max(lo);
+        range.1 = match (range.1, hi) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    };
+
+    match step.cmp(&0) {
+        Ordering::Equal => return base >= 0 && max.is_none_or(|max| base <= max),
+        Ordering::Greater => tighten(
+            Some(-Integer::div_floor(&base, &step)),
+            max.map(|max| Integer::div_floor(&(max - base), &step)),
+        ),
+        Ordering::Less => tighten(
+            max.map(|max| -Integer::div_floor(&(max - base), &-step)),
+            Some(Integer::div_floor(&base, &-step)),
+        ),
+    }
+
+    true
+}
+
+// Token cost of each button, and how often either may be pressed
+#[derive(Debug, Clone, Copy)]
+pub struct Pricing {
+    pub a: isize,
+    pub b: isize,
+    pub max_presses: Option<isize>,
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        Self { a: 3, b: 1, max_presses: None }
+    }
 }
 
 #[aoc_generator(day13)]
@@ -66,30 +207,22 @@ pub fn input_generator(input: &str) -> Vec<Crane> {
         }).collect()
 }
 
-#[aoc(day13, part1)]
-pub fn solve_part1(input: &Vec<Crane>) -> isize {
-    input
+fn total_cost(cranes: &[Crane], offset: isize) -> isize {
+    let pricing = Pricing::default();
+    cranes
         .iter()
-        .map(|crane| {
-            if let Some((m, n)) = crane.solve(0) {
-                m * 3 + n
-            } else {
-                0
-            }
-        }).sum()
+        .filter_map(|crane| crane.solve(offset, &pricing).map(|presses| crane.cost(presses, &pricing)))
+        .sum()
+}
+
+#[aoc(day13, part1)]
+pub fn solve_part1(input: &[Crane]) -> isize {
+    total_cost(input, 0)
 }
 
 #[aoc(day13, part2)]
-pub fn solve_part2(input: &Vec<Crane>) -> isize {
-    input
-        .iter()
-        .map(|crane| {
-            if let Some((m, n)) = crane.solve(10_000_000_000_000) {
-                m * 3 + n
-            } else {
-                0
-            }
-        }).sum()
+pub fn solve_part2(input: &[Crane]) -> isize {
+    total_cost(input, 10_000_000_000_000)
 }
 
 #[cfg(test)]
@@ -116,4 +249,60 @@ Prize: X=18641, Y=10279";
     fn part1_test() {
         assert_eq!(solve_part1(&input_generator(TEST)), 480);
     }
+
+    #[test]
+    fn press_limit_test() {
+        let cranes = input_generator(TEST);
+        let capped = Pricing { max_presses: Some(79), ..Pricing::default() };
+        assert_eq!(cranes[0].solve(0, &Pricing::default()), Some((80, 40)));
+        assert_eq!(cranes[0].solve(0, &capped), None);
+        assert_eq!(cranes[1].solve(0, &Pricing::default()), None);
+    }
+
+    #[test]
+    fn collinear_test() {
+        let crane = Crane { a: (2, 2), b: (1, 1), p: (10, 10) };
+        assert_eq!(crane.solve(0, &Pricing::default()), Some((0, 10)));
+        assert_eq!(crane.solve(0, &Pricing { max_presses: Some(5), ..Pricing::default() }), Some((3, 4)));
+        assert_eq!(crane.solve(0, &Pricing { a: 1, b: 3, max_presses: None }), Some((5, 0)));
+        assert_eq!(crane.solve(1, &Pricing::default()), Some((0, 11)));
+
+        let crane = Crane { a: (3, 6), b: (5, 10), p: (7, 14) };
+        assert_eq!(crane.solve(0, &Pricing::default()), None);
+        let crane = Crane { a: (3, 6), b: (5, 10), p: (8, 16) };
+        assert_eq!(crane.solve(0, &Pricing::default()), Some((1, 1)));
+        let crane = Crane { a: (3, 6), b: (5, 10), p: (8, 15) };
+        assert_eq!(crane.solve(0, &Pricing::default()), None);
+        let crane = Crane { a: (0, 0), b: (0, 4), p: (0, 12) };
+        assert_eq!(crane.solve(0, &Pricing::default()), Some((0, 3)));
+    }
+
+    #[cfg(feature = "z3")]
+    #[test]
+    fn ilp_cross_check_test() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(13);
+        for _ in 0..500 {
+            let a = (rng.gen_range(0..20), rng.gen_range(0..20));
+            // Every other machine gets collinear buttons, some multiple of a's smallest step
+            let b = if rng.gen_bool(0.5) {
+                let (k, num) = (a.0.gcd(&a.1).max(1), rng.gen_range(1..6));
+                (a.0 / k * num, a.1 / k * num)
+            } else {
+                (rng.gen_range(0..20), rng.gen_range(0..20))
+            };
+            let presses = (rng.gen_range(0..50), rng.gen_range(0..50));
+            let nudge = rng.gen_range(0..2);
+            let crane = Crane { a, b, p: (presses.0 * a.0 + presses.1 * b.0 + nudge, presses.0 * a.1 + presses.1 * b.1) };
+            let pricing = Pricing {
+                a: rng.gen_range(0..5),
+                b: rng.gen_range(0..5),
+                max_presses: rng.gen_bool(0.3).then(|| rng.gen_range(0..60)),
+            };
+
+            let cost = |presses: Option<(isize, isize)>| presses.map(|presses| crane.cost(presses, &pricing));
+            assert_eq!(cost(crane.solve(0, &pricing)), cost(crane.solve_ilp(0, &pricing)), "{:?} {:?}", crane, pricing);
+        }
+    }
 }
\ No newline at end of file
@@ -1,106 +1,257 @@
 use crate::prelude::*;
+use image::{GrayImage, Luma};
+use num::integer::{gcd, lcm, ExtendedGcd, Integer};
 
+#[derive(Debug, Clone, Copy)]
 pub struct Robot {
     pos: Point,
     vel: Point,
 }
 
+impl Robot {
+    pub fn new(pos: Point, vel: Point) -> Self {
+        Self { pos, vel }
+    }
+
+    fn axis(&self, axis: Axis) -> (i64, i64) {
+        match axis {
+            Axis::X => (self.pos.x as i64, self.vel.x as i64),
+            Axis::Y => (self.pos.y as i64, self.vel.y as i64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+// How clumped the robots are along an axis, lower being more orderly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Variance,
+    Entropy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub frame: usize,
+    pub score: f64,
+    pub x_score: f64,
+    pub y_score: f64,
+}
+
+// Robots on a wrapping floor of any size
+#[derive(Debug, Clone)]
+pub struct Swarm {
+    pub robots: Vec<Robot>,
+    pub bounds: (i32, i32),
+}
+
+impl Swarm {
+    pub fn new(robots: Vec<Robot>, bounds: (i32, i32)) -> Self {
+        Self { robots, bounds }
+    }
+
+    pub fn with_bounds(self, bounds: (i32, i32)) -> Self {
+        Self { bounds, ..self }
+    }
+
+    // Where everyone is after some number of steps. Each axis wraps on its own, and is worked
+    // out in i64 so big floors don't overflow.
+    pub fn positions(&self, frame: usize) -> Vec<Point> {
+        let axis = |pos: i32, vel: i32, size: i32| {
+            let steps = (frame % size as usize) as i64;
+            (pos as i64 + vel as i64 * steps).rem_euclid(size as i64) as i32
+        };
+        self.robots.iter()
+            .map(|robot| Point::new(
+                axis(robot.pos.x, robot.vel.x, self.bounds.0),
+                axis(robot.pos.y, robot.vel.y, self.bounds.1),
+            ))
+            .collect()
+    }
+
+    pub fn advance(&mut self, steps: usize) {
+        let positions = self.positions(steps);
+        for (robot, pos) in self.robots.iter_mut().zip(positions) {
+            robot.pos = pos;
+        }
+    }
+
+    // Robots inside a region of the floor
+    pub fn count_in(&self, frame: usize, region: impl Fn(&Point) -> bool) -> usize {
+        self.positions(frame).iter().filter(|pos| region(pos)).count()
+    }
+
+    // Robots inside an inclusive rectangle
+    pub fn count_in_rect(&self, frame: usize, min: Point, max: Point) -> usize {
+        self.count_in(frame, |pos| pos.x >= min.x && pos.x <= max.x && pos.y >= min.y && pos.y <= max.y)
+    }
+
+    // [tl, tr, bl, br], ignoring anyone on the middle lines
+    pub fn quadrants(&self, frame: usize) -> [usize; 4] {
+        let (mid_x, mid_y) = (self.bounds.0 / 2, self.bounds.1 / 2);
+        let mut quadrants: [usize; 4] = [0; 4];
+
+        for pos in self.positions(frame) {
+            if pos.x != mid_x && pos.y != mid_y {
+                let index = match (pos.x < mid_x, pos.y < mid_y) {
+                    (true, true) => 0,
+                    (true, false) => 2,
+                    (false, true) => 1,
+                    (false, false) => 3,
+                };
+                quadrants[index] += 1;
+            }
+        }
+
+        quadrants
+    }
+
+    pub fn safety_factor(&self, frame: usize) -> usize {
+        self.quadrants(frame).iter().product()
+    }
+
+    // Safety factor for every frame over a full cycle of the swarm
+    pub fn safety_curve(&self) -> Vec<usize> {
+        let cycle = lcm(self.period(Axis::X), self.period(Axis::Y));
+        (0..cycle).map(|frame| self.safety_factor(frame)).collect()
+    }
+
+    // Number of robots on each tile
+    pub fn render(&self, frame: usize) -> Grid<usize> {
+        let mut grid = Grid::new_fill(self.bounds.0 as usize, self.bounds.1 as usize, 0);
+        for pos in self.positions(frame) {
+            grid[pos.as_utup()] += 1;
+        }
+
+        grid
+    }
+
+    // Occupied tiles in white
+    pub fn image(&self, frame: usize) -> GrayImage {
+        let grid = self.render(frame);
+        GrayImage::from_fn(grid.width as u32, grid.height as u32, |x, y| {
+            Luma([if grid[(x as usize, y as usize)] > 0 { 255 } else { 0 }])
+        })
+    }
+
+    fn axis_bound(&self, axis: Axis) -> i64 {
+        match axis {
+            Axis::X => self.bounds.0 as i64,
+            Axis::Y => self.bounds.1 as i64,
+        }
+    }
+
+    // Frames until every robot is back where it started along an axis
+    pub fn period(&self, axis: Axis) -> usize {
+        let bound = self.axis_bound(axis);
+        self.robots.iter()
+            .map(|robot| bound / gcd(robot.axis(axis).1.rem_euclid(bound), bound))
+            .fold(1, lcm) as usize
+    }
+
+    fn axis_score(&self, axis: Axis, frame: usize, metric: Metric) -> f64 {
+        let bound = self.axis_bound(axis);
+        let positions = self.robots.iter().map(|robot| {
+            let (p, v) = robot.axis(axis);
+            (p + v * frame as i64).rem_euclid(bound)
+        });
+
+        match metric {
+            Metric::Variance => {
+                let (n, sum, sum_sq) = positions.fold((0.0, 0.0, 0.0), |(n, sum, sum_sq), p| {
+                    (n + 1.0, sum + p as f64, sum_sq + (p * p) as f64)
+                });
+                sum_sq / n - (sum / n).powi(2)
+            },
+            Metric::Entropy => {
+                let mut counts = vec![0; bound as usize];
+                positions.for_each(|p| counts[p as usize] += 1);
+                let n = self.robots.len() as f64;
+                counts.iter()
+                    .filter(|&&count| count > 0)
+                    .map(|&count| {
+                        let p = count as f64 / n;
+                        -p * p.log2()
+                    })
+                    .sum()
+            },
+        }
+    }
+
+    // Each axis wraps independently, so find the most orderly frames of either period
+    // separately, then line them up with the Chinese Remainder Theorem. Scores are relative
+    // to the axis average, so below 2 means more orderly than usual, and the best comes first.
+    pub fn candidates(&self, metric: Metric, per_axis: usize) -> Vec<Candidate> {
+        let ranked = |axis: Axis| {
+            let period = self.period(axis);
+            let scores: Vec<f64> = (0..period).map(|frame| self.axis_score(axis, frame, metric)).collect();
+            let mean = scores.iter().sum::<f64>() / period as f64;
+            let mut frames: Vec<(usize, f64)> = scores.into_iter()
+                .enumerate()
+                .map(|(frame, score)| (frame, if mean > 0.0 { score / mean } else { 1.0 }))
+                .collect();
+            frames.sort_by(|a, b| a.1.total_cmp(&b.1));
+            frames.truncate(per_axis);
+            (period, frames)
+        };
+
+        let (x_period, x_frames) = ranked(Axis::X);
+        let (y_period, y_frames) = ranked(Axis::Y);
+
+        let mut candidates: Vec<Candidate> = x_frames.iter()
+            .flat_map(|&(x_frame, x_score)| y_frames.iter().filter_map(move |&(y_frame, y_score)| {
+                let frame = crt((x_frame as i64, x_period as i64), (y_frame as i64, y_period as i64))?;
+                Some(Candidate { frame: frame as usize, score: x_score + y_score, x_score, y_score })
+            }))
+            .collect();
+        candidates.sort_by(|a, b| a.score.total_cmp(&b.score).then(a.frame.cmp(&b.frame)));
+
+        candidates
+    }
+}
+
+// Smallest t >= 0 with t = a (mod m) and t = b (mod n), moduli needn't be coprime
+pub fn crt((a, m): (i64, i64), (b, n): (i64, i64)) -> Option<i64> {
+    let ExtendedGcd { gcd, x, .. } = m.extended_gcd(&n);
+    if (b - a) % gcd != 0 {
+        return None;
+    }
+    let modulus = m / gcd * n;
+    let step = ((b - a) / gcd * x).rem_euclid(n / gcd);
+
+    Some((a + m * step).rem_euclid(modulus))
+}
+
 #[aoc_generator(day14)]
-pub fn input_generator(input: &str) -> Vec<Robot> {
-    input
+pub fn input_generator(input: &str) -> Swarm {
+    let robots = input
         .lines()
         .map(|line| {
             let parts = line.split_once(' ').unwrap();
             let pos: Vec<i32> = parts.0[2..].split(',').map(|num| num.parse::<i32>().unwrap()).collect();
             let vel: Vec<i32> = parts.1[2..].split(',').map(|num| num.parse::<i32>().unwrap()).collect();
-            Robot { pos: Point::new(pos[0], pos[1]), vel: Point::new(vel[0], vel[1]) }
+            Robot::new(Point::new(pos[0], pos[1]), Point::new(vel[0], vel[1]))
         })
-        .collect()
+        .collect();
+
+    Swarm::new(robots, (101, 103))
 }
 
 #[aoc(day14, part1)]
-pub fn solve_part1(input: &Vec<Robot>) -> usize {
-    let mut pos = Vec::new();
-    // Test boundary
-    // let bounday: (i32, i32) = (11, 7);
-    // Actual boundary
-    let bounday: (i32, i32) = (101, 103);
-    let steps = 100;
-
-    for robot in input {
-        let x = robot.pos.x + robot.vel.x * steps;
-        let y = robot.pos.y + robot.vel.y * steps;
-        let p = (
-            ((x % bounday.0) + bounday.0) % bounday.0,
-            ((y % bounday.1) + bounday.1) % bounday.1,
-        );
-        pos.push(p);
-    }
-
-    let mid_x = bounday.0 / 2;
-    let mid_y = bounday.1 / 2; 
-    
-    // [tl, tr, bl, br]
-    let mut quadrants: [usize; 4] = [0; 4];
-
-    pos.iter().for_each(|p| {
-        if p.0 != mid_x && p.1 != mid_y {
-            let index = match (p.0 < mid_x, p.1 < mid_y) {
-                (true, true) => 0,
-                (true, false) => 2,
-                (false, true) => 1,
-                (false, false) => 3,
-            };
-            quadrants[index] += 1;
-        }
-    });
-
-    quadrants.iter().product()
+pub fn solve_part1(swarm: &Swarm) -> usize {
+    swarm.safety_factor(100)
 }
 
+// This used to advance until no two robots overlapped, which happened to work. 4 hours to
+// get to that point :...( I tried all sorts of shit, including rendering every frame as an
+// image to create an animation. The tree is the one frame where both axes bunch up.
 #[aoc(day14, part2)]
-pub fn solve_part2(input: &Vec<Robot>) -> usize {
-    let boundary: (i32, i32) = (101, 103);
-    let mut i = 0;
-    'outer: loop {
-        let mut pos = HashSet::new();
-
-        for robot in input {
-            let x = robot.pos.x + robot.vel.x * i as i32;
-            let y = robot.pos.y + robot.vel.y * i as i32;
-            let p = (
-                ((x % boundary.0) + boundary.0) % boundary.0,
-                ((y % boundary.1) + boundary.1) % boundary.1,
-            );
-            if !pos.contains(&p) {
-                pos.insert(p);
-            } else {
-                break;
-            }
-        }
-
-        // 4 hours to get to this point :...(
-        // I tried all sorts of shit, including rendering every frame as an image to create an animation
-        if pos.len() == input.len() {           
-            // Uncomment if you want to see the tree
-            // for y in 0..boundary.1 {
-            //     for x in 0..boundary.0 {
-            //         if pos.contains(&(x, y)) {
-            //             print!("#");
-            //         } else {
-            //             print!(".");
-            //         }
-            //     }
-            //     println!();
-            // }
-            // println!("Iteration {}", i);
-            break 'outer;
-        }
-
-        i += 1;
-    }
-
-    i
+pub fn solve_part2(swarm: &Swarm) -> usize {
+    swarm.candidates(Metric::Variance, 1)[0].frame
 }
 
 #[cfg(test)]
@@ -122,6 +273,62 @@ p=9,5 v=-3,-3";
 
     #[test]
     fn part1_test() {
-        assert_eq!(solve_part1(&input_generator(TEST)), 12);
+        assert_eq!(solve_part1(&input_generator(TEST).with_bounds((11, 7))), 12);
+    }
+
+    #[test]
+    fn swarm_test() {
+        let mut swarm = input_generator(TEST).with_bounds((11, 7));
+        assert_eq!(swarm.quadrants(100), [1, 3, 4, 1]);
+        assert_eq!(swarm.count_in_rect(100, Point::new(0, 0), Point::new(10, 0)), 3);
+        assert_eq!(swarm.render(100)[(6usize, 0usize)], 2);
+        assert_eq!(swarm.image(100).get_pixel(6, 0), &Luma([255]));
+
+        // Going a long way round is the same as going the short way
+        let positions = swarm.positions(100 + 77 * 1000);
+        swarm.advance(60);
+        swarm.advance(40);
+        assert_eq!(swarm.positions(0), positions);
+
+        // Huge floors used to overflow
+        let big = Swarm::new(vec![Robot { pos: Point::new(1, 2), vel: Point::new(-3, 99) }], (50_021, 49_999));
+        let frame = 1_000_000_007;
+        let x = (1 - 3 * frame as i64).rem_euclid(50_021) as i32;
+        let y = (2 + 99 * frame as i64).rem_euclid(49_999) as i32;
+        assert_eq!(big.positions(frame), vec![Point::new(x, y)]);
+    }
+
+    #[test]
+    fn safety_curve_test() {
+        let curve = input_generator(TEST).with_bounds((11, 7)).safety_curve();
+        assert_eq!(curve.len(), 77);
+        assert_eq!(curve[100 % 77], 12);
+    }
+
+    #[test]
+    fn crt_test() {
+        assert_eq!(crt((2, 3), (3, 5)), Some(8));
+        assert_eq!(crt((1, 4), (3, 6)), Some(9));
+        assert_eq!(crt((0, 4), (1, 6)), None);
+    }
+
+    #[test]
+    fn candidates_test() {
+        // Everyone converges on the middle at frame 40 and nowhere else in the cycle
+        let robots: Vec<Robot> = [(1, 2), (-3, 1), (4, -2), (2, 3), (-1, -1), (5, 2)]
+            .into_iter()
+            .map(|(vx, vy)| {
+                let vel = Point::new(vx, vy);
+                Robot::new((Point::new(5, 3) - vel * 40).wrap((11, 7)), vel)
+            })
+            .collect();
+        let swarm = Swarm::new(robots, (11, 7));
+
+        assert_eq!((swarm.period(Axis::X), swarm.period(Axis::Y)), (11, 7));
+        for metric in [Metric::Variance, Metric::Entropy] {
+            let ranked = swarm.candidates(metric, 3);
+            assert_eq!(ranked.len(), 9);
+            assert_eq!((ranked[0].frame, ranked[0].score), (40, 0.0));
+        }
     }
 }
\ No newline at end of file
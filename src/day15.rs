@@ -3,29 +3,30 @@ use crate::prelude::*;
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Map {
     Box,
-    LBox,
-    RBox,
     Floor,
     Wall,
 }
 
-pub struct Robot {
-    x: usize,
-    y: usize,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tile {
+    Crate(usize),
+    Floor,
+    Wall,
 }
 
-impl Robot {
-    fn new(pos: &(usize, usize)) -> Self {
-        Self { x: pos.0, y: pos.1 }
-    }
-
-    fn step(&mut self, dir: &(i32, i32)) {
-        self.x = (self.x as i32 + dir.0) as usize;
-        self.y = (self.y as i32 + dir.1) as usize;
-    }
+// Any shape of box, moved as one piece
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crate {
+    pub id: usize,
+    pub cells: Vec<(usize, usize)>,
+}
 
-    fn pos(&self) -> (usize, usize) {
-        (self.x, self.y)
+impl Crate {
+    // Top left of the bounding box, which is what the GPS measures to
+    pub fn corner(&self) -> (usize, usize) {
+        let x = self.cells.iter().map(|cell| cell.0).min().unwrap();
+        let y = self.cells.iter().map(|cell| cell.1).min().unwrap();
+        (x, y)
     }
 }
 
@@ -33,99 +34,232 @@ fn offset(from: &(usize, usize), dir: &(i32, i32)) -> (usize, usize) {
     ((from.0 as i32 + dir.0) as usize, (from.1 as i32 + dir.1) as usize)
 }
 
-// fn draw_map(map: &Grid<Map>, robot: &Robot) {
-//     println!("Width: {}, height: {}", map.width, map.height);
-//     for row in 0..map.height {
-//         for col in 0..map.width {
-//             let ch = match map[(col, row)] {
-//                 Map::Box => 'O',
-//                 Map::LBox => '[',
-//                 Map::RBox => ']',
-//                 Map::Floor => '.',
-//                 Map::Wall => '#',
-//             };
-//             if col == robot.x && row == robot.y {
-//                 print!("@");
-//             } else {
-//                 print!("{ch}");
-//             }
-//         }
-//         println!()
-//     }
-// }
-
-fn moveable(map: &Grid<Map>, dir: &(i32, i32), pos: (usize, usize)) -> bool {
-    if let Ok(tile) = map.peek(&pos, dir) {
-        let next = offset(&pos, dir);
-        match tile {
-            Map::Floor => return true,
-            Map::Wall => return false,
-            Map::Box => return true,
-            Map::LBox => {
-                return moveable(map, dir, (next.0, next.1)) && moveable(map, dir, (next.0 + 1, next.1))
-            },
-            Map::RBox => {
-                return moveable(map, dir, (next.0 - 1, next.1)) && moveable(map, dir, (next.0, next.1))
-            },
+// Everything needed to take a move back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Push {
+    pub dir: Ortho,
+    pub robot: (usize, usize),
+    pub moved: Vec<usize>,
+    pub blocked: bool,
+}
+
+pub struct Warehouse {
+    tiles: Grid<Tile>,
+    crates: Vec<Crate>,
+    robot: (usize, usize),
+    history: Vec<Push>,
+}
+
+impl Warehouse {
+    pub fn new(map: &Grid<Map>, robot: (usize, usize)) -> Self {
+        Self::scaled(map, robot, (1, 1))
+    }
+
+    // Everything but the robot twice as wide
+    pub fn widen(map: &Grid<Map>, robot: (usize, usize)) -> Self {
+        Self::scaled(map, robot, (2, 1))
+    }
+
+    // Every tile blown up to sx by sy, with each box becoming a single crate that size.
+    // The robot stays one tile, in the top left of where it was.
+    pub fn scaled(map: &Grid<Map>, robot: (usize, usize), (sx, sy): (usize, usize)) -> Self {
+        let mut warehouse = Self::empty(map.width * sx, map.height * sy, (robot.0 * sx, robot.1 * sy));
+
+        for y in 0..map.height {
+            for x in 0..map.width {
+                let cells: Vec<(usize, usize)> = (0..sy)
+                    .flat_map(|dy| (0..sx).map(move |dx| (x * sx + dx, y * sy + dy)))
+                    .collect();
+                match map[(x, y)] {
+                    Map::Box => { warehouse.add_crate(cells); },
+                    Map::Wall => warehouse.tiles.place_at(&cells, Tile::Wall),
+                    Map::Floor => (),
+                }
+            }
         }
+
+        warehouse
     }
 
-    false
-}
+    // '#' walls, '@' the robot, 'O' and '[]' the usual boxes, and any other character is a
+    // crate made up of every touching cell with the same character
+    pub fn from_layout(layout: &str) -> Self {
+        let rows: Vec<Vec<char>> = layout.lines().map(|line| line.chars().collect()).collect();
+        let mut warehouse = Self::empty(rows[0].len(), rows.len(), (0, 0));
+        let mut seen = HashSet::new();
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &ch) in row.iter().enumerate() {
+                if !seen.insert((x, y)) {
+                    continue;
+                }
+                match ch {
+                    '.' | ']' => (),
+                    '#' => warehouse.tiles[(x, y)] = Tile::Wall,
+                    '@' => warehouse.robot = (x, y),
+                    'O' => { warehouse.add_crate(vec![(x, y)]); },
+                    '[' => {
+                        seen.insert((x + 1, y));
+                        warehouse.add_crate(vec![(x, y), (x + 1, y)]);
+                    },
+                    _ => {
+                        let mut cells = vec![(x, y)];
+                        let mut stack = vec![(x, y)];
+                        while let Some(pos) = stack.pop() {
+                            for next in warehouse.tiles.neighbours(&pos) {
+                                if rows[next.1][next.0] == ch && seen.insert(next) {
+                                    cells.push(next);
+                                    stack.push(next);
+                                }
+                            }
+                        }
+                        warehouse.add_crate(cells);
+                    },
+                }
+            }
+        }
 
-// North and south only
-fn get_group(map: &Grid<Map>, dir: &(i32, i32), pos: (usize, usize)) -> HashSet<(usize, usize)> {
-    let mut group = HashSet::new();
-
-    match map[pos] {
-        Map::LBox => {
-            let right = (pos.0 + 1, pos.1);
-            group.insert(pos);
-            group.insert(right);
-            group.extend(get_group(map, dir, offset(&pos, dir)));
-            group.extend(get_group(map, dir, offset(&right, dir)));
-        },
-        Map::RBox => {
-            let left = (pos.0 - 1, pos.1);
-            group.insert(pos);
-            group.insert(left);
-            group.extend(get_group(map, dir, offset(&pos, dir)));
-            group.extend(get_group(map, dir, offset(&left, dir)));
-        },
-        _ => {},
-    };
-
-    return group
-}
+        warehouse
+    }
 
-fn rebundle_positions(hash_set: &HashSet<(usize, usize)>, direction: &(i32, i32)) -> Vec<Vec<(usize, usize)>> {
-    let mut sorted_vectors = Vec::new();
-    let mut y_to_coords = HashMap::new();
+    fn empty(width: usize, height: usize, robot: (usize, usize)) -> Self {
+        Self { tiles: Grid::new_fill(width, height, Tile::Floor), crates: Vec::new(), robot, history: Vec::new() }
+    }
+
+    // Drops a crate onto the floor, returning its id
+    pub fn add_crate(&mut self, cells: Vec<(usize, usize)>) -> usize {
+        let id = self.crates.len();
+        self.tiles.place_at(&cells, Tile::Crate(id));
+        self.crates.push(Crate { id, cells });
+        id
+    }
+
+    pub fn crates(&self) -> &[Crate] {
+        &self.crates
+    }
+
+    pub fn robot(&self) -> (usize, usize) {
+        self.robot
+    }
 
-    for &(x, y) in hash_set.iter() {
-        y_to_coords.entry(y).or_insert(Vec::new()).push((x, y));
+    pub fn history(&self) -> &[Push] {
+        &self.history
     }
 
-    let mut sorted_y_values: Vec<usize> = y_to_coords.keys().cloned().collect();
-    sorted_y_values.sort_by(|a, b| {
-        if direction.1 == -1 {
-            a.cmp(b)
-        } else {
-            b.cmp(a)
+    // Anything off the edge counts as wall
+    fn tile(&self, pos: (usize, usize)) -> Tile {
+        if pos.0 < self.tiles.width && pos.1 < self.tiles.height { self.tiles[pos] } else { Tile::Wall }
+    }
+
+    // Crates in the way of a push, nearest first, or None if any of them hits a wall
+    fn pushed(&self, dir: &(i32, i32)) -> Option<Vec<usize>> {
+        let mut moved = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([offset(&self.robot, dir)]);
+
+        while let Some(pos) = queue.pop_front() {
+            match self.tile(pos) {
+                Tile::Wall => return None,
+                Tile::Floor => (),
+                Tile::Crate(id) => {
+                    if seen.insert(id) {
+                        moved.push(id);
+                        queue.extend(self.crates[id].cells.iter().map(|cell| offset(cell, dir)));
+                    }
+                },
+            }
         }
-    });
 
-    for y in sorted_y_values {
-        if let Some(coords) = y_to_coords.get(&y) {
-            sorted_vectors.push(coords.clone());
+        Some(moved)
+    }
+
+    // Lifts everything off the floor before putting it down again, so order doesn't matter
+    fn shift(&mut self, ids: &[usize], dir: &(i32, i32)) {
+        for &id in ids {
+            self.tiles.place_at(&self.crates[id].cells, Tile::Floor);
         }
+        for &id in ids {
+            let cells = &mut self.crates[id].cells;
+            cells.iter_mut().for_each(|cell| *cell = offset(cell, dir));
+            self.tiles.place_at(cells.iter(), Tile::Crate(id));
+        }
+    }
+
+    // Tries to move the robot, returning the ids of any crates it pushed along
+    pub fn step(&mut self, dir: Ortho) -> Vec<usize> {
+        let delta = dir.delta();
+        let mut push = Push { dir, robot: self.robot, moved: Vec::new(), blocked: true };
+
+        if let Some(moved) = self.pushed(&delta) {
+            self.shift(&moved, &delta);
+            self.robot = offset(&self.robot, &delta);
+            push.moved = moved;
+            push.blocked = false;
+        }
+        let moved = push.moved.clone();
+        self.history.push(push);
+
+        moved
+    }
+
+    pub fn run(&mut self, moves: &[Ortho]) {
+        for &dir in moves {
+            self.step(dir);
+        }
+    }
+
+    pub fn undo(&mut self) -> Option<Push> {
+        let push = self.history.pop()?;
+        self.shift(&push.moved, &push.dir.flip().delta());
+        self.robot = push.robot;
+
+        Some(push)
+    }
+
+    // Winds back to just before move `index`, then plays the rest of the moves from there
+    pub fn replay(&mut self, moves: &[Ortho], index: usize) {
+        while self.history.len() > index {
+            self.undo();
+        }
+        self.run(&moves[self.history.len()..]);
+    }
+
+    pub fn gps(&self) -> usize {
+        self.crates.iter()
+            .map(|crate_| {
+                let (x, y) = crate_.corner();
+                100 * y + x
+            })
+            .sum()
     }
 
-    sorted_vectors
+    // Single boxes as 'O', wide ones as '[]' and anything else lettered by id
+    pub fn draw(&self) -> String {
+        let mut out = String::new();
+        for row in 0..self.tiles.height {
+            for col in 0..self.tiles.width {
+                out.push(match self.tiles[(col, row)] {
+                    _ if (col, row) == self.robot => '@',
+                    Tile::Floor => '.',
+                    Tile::Wall => '#',
+                    Tile::Crate(id) => match self.crates[id].cells.as_slice() {
+                        [_] => 'O',
+                        [left, right] if left.1 == right.1 && left.0 + 1 == right.0 => {
+                            if *left == (col, row) { '[' } else { ']' }
+                        },
+                        _ => (b'a' + (id % 26) as u8) as char,
+                    },
+                });
+            }
+            out.push('\n');
+        }
+
+        out
+    }
 }
 
 #[aoc_generator(day15)]
-pub fn input_generator(input: &str) -> (Grid<Map>, (usize, usize), Vec<(i32, i32)>) {
+pub fn input_generator(input: &str) -> (Grid<Map>, (usize, usize), Vec<Ortho>) {
     let (map_str, move_str) = input.split_once("\n\n").unwrap();
     let width = map_str.lines().next().unwrap().len();
     let height = map_str.lines().count();
@@ -150,10 +284,10 @@ pub fn input_generator(input: &str) -> (Grid<Map>, (usize, usize), Vec<(i32, i32
 
     let moves = move_str.lines().flat_map(|line| {
         line.chars().map(|ch| match ch {
-            '^' => (0, -1),
-            '>' => (1, 0),
-            'v' => (0, 1),
-            '<' => (-1, 0),
+            '^' => Ortho::North,
+            '>' => Ortho::East,
+            'v' => Ortho::South,
+            '<' => Ortho::West,
             _ => unreachable!(),
         })
     }).collect();
@@ -164,155 +298,21 @@ pub fn input_generator(input: &str) -> (Grid<Map>, (usize, usize), Vec<(i32, i32
 #[aoc(day15, part1)]
 pub fn solve_part1(
     (grid, start, moves): &(
-    Grid<Map>, (usize, usize), Vec<(i32, i32)>
+    Grid<Map>, (usize, usize), Vec<Ortho>
 )) -> usize {
-    let mut map = Grid::new(grid.width, grid.height, grid.entity.clone());
-
-    for (idx, value) in grid.entity.iter().enumerate() {
-        map.entity[idx] = *value;
-    }
-    let mut robot = Robot::new(start);
-
-    for m in moves {
-        match map.peek(&robot.pos(), &m) {
-            Ok(tile) => {
-                match tile {
-                    Map::Box   => {
-                        let mut current_pos = robot.pos();
-                        let mut boxes = Vec::new();
-
-                        while let Ok(tile) = map.peek(&current_pos, m) {
-                            match tile {
-                                Map::Box => {
-                                    boxes.push(((offset(&current_pos, m)), tile));
-                                    current_pos = offset(&current_pos, m);
-                                },
-                                Map::Floor => {
-                                    boxes.push(((offset(&current_pos, m)), tile));
-                                    break;
-                                },
-                                Map::Wall => break,
-                                _ => unimplemented!(),
-                            }
-                        }
-                        if let Some(end) = boxes.pop() {
-                            if end.1 == Map::Floor {
-                                for b in boxes.iter().rev() {
-                                    map.slide(b.0, m.clone(), Some(Map::Floor)).ok();
-                                }
-                                robot.step(m);
-                            }
-                        }
-                    },
-                    Map::Floor => robot.step(m),
-                    Map::Wall  => continue,
-                    _ => unimplemented!(),
-                }
-            },
-            Err(_) => continue,
-        }
-    }
-
-    let mut score = 0;
-    for y in 0..map.height {
-        for x in 0..map.width {
-            if map[(x, y)] == Map::Box {
-                score += 100 * y + x;
-            }
-        }
-    }
-
-    score
+    let mut warehouse = Warehouse::new(grid, *start);
+    warehouse.run(moves);
+    warehouse.gps()
 }
 
 #[aoc(day15, part2)]
 pub fn solve_part2(
     (grid, start, moves): &(
-    Grid<Map>, (usize, usize), Vec<(i32, i32)>
+    Grid<Map>, (usize, usize), Vec<Ortho>
 )) -> usize {
-    let mut new_entity = Vec::new();
-
-    for value in grid.entity.iter() {
-        match value {
-            Map::Wall | Map::Floor => {
-                new_entity.push(*value);
-                new_entity.push(*value);
-            },
-            Map::Box => {
-                new_entity.push(Map::LBox);
-                new_entity.push(Map::RBox);
-            },
-            _ => unimplemented!(),
-        }
-    }
-
-    let mut map = Grid::new(grid.width * 2, grid.height, new_entity);
-    let mut robot = Robot::new(&(start.0 * 2, start.1));
-
-    for m in moves {
-        match map.peek(&robot.pos(), &m) {
-            Ok(tile) => {
-                match tile {
-                    Map::LBox | Map::RBox   => {
-                        if m == &(-1, 0) || m == &(1, 0) { // Left-right
-                            let mut current_pos = robot.pos();
-                            let mut boxes = Vec::new();
-
-                            while let Ok(tile) = map.peek(&current_pos, m) {
-                                match tile {
-                                    Map::LBox | Map::RBox => {
-                                        boxes.push(((offset(&current_pos, m)), tile));
-                                        current_pos = offset(&current_pos, m);
-                                    },
-                                    Map::Floor => {
-                                        boxes.push(((offset(&current_pos, m)), tile));
-                                        break;
-                                    },
-                                    Map::Wall => break,
-                                    _ => unimplemented!(),
-                                }
-                            }
-                            if let Some(end) = boxes.pop() {
-                                if end.1 == Map::Floor {
-                                    for b in boxes.iter().rev() {
-                                        map.slide(b.0, m.clone(), Some(Map::Floor)).ok();
-                                    }
-                                    robot.step(m);
-                                }
-                            }
-                        } else { // Up-down
-                            if moveable(&map, m, robot.pos()) {
-                                let group = get_group(&map, m, offset(&robot.pos(), m));
-                                let bundle = rebundle_positions(&group, m);
-                                for row in bundle {
-                                    for pos in row {
-                                        map.slide(pos, m.clone(), Some(Map::Floor)).ok();
-                                    }
-                                }
-                                robot.step(m);
-                            }
-                        }
-                    },
-                    Map::Floor => robot.step(m),
-                    Map::Wall  => continue,
-                    _ => unimplemented!(),
-                }
-            },
-            Err(_) => continue,
-        }
-        // draw_map(&map, &robot);
-    }
-    
-    let mut score = 0;
-    for y in 0..map.height {
-        for x in 0..map.width {
-            if map[(x, y)] == Map::LBox {
-                score += 100 * y + x;
-            }
-        }
-    }
-
-    score
+    let mut warehouse = Warehouse::widen(grid, *start);
+    warehouse.run(moves);
+    warehouse.gps()
 }
 
 #[cfg(test)]
@@ -381,4 +381,75 @@ v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^";
     fn part2_test3() {
         assert_eq!(solve_part2(&input_generator(TEST3)), 618);
     }
+
+    #[test]
+    fn step_test() {
+        let (grid, start, _) = input_generator(TEST3);
+        let mut warehouse = Warehouse::widen(&grid, start);
+        assert_eq!(warehouse.step(Ortho::West), vec![1, 0]);
+        assert_eq!(warehouse.step(Ortho::South), vec![]);
+        assert_eq!(warehouse.step(Ortho::South), vec![]);
+        assert_eq!(warehouse.step(Ortho::West), vec![]);
+        assert_eq!(warehouse.step(Ortho::West), vec![]);
+        assert_eq!(warehouse.step(Ortho::North), vec![2, 0, 1]);
+        assert_eq!(warehouse.robot(), (7, 4));
+        assert_eq!(warehouse.step(Ortho::North), vec![]);
+        assert!(warehouse.history().last().unwrap().blocked);
+    }
+
+    #[test]
+    fn polyomino_test() {
+        let mut warehouse = Warehouse::from_layout("\
+########
+#......#
+#.O.bb.#
+#.aabb.#
+#@a....#
+#.a....#
+########");
+        assert_eq!(warehouse.crates().iter().map(|c| c.cells.len()).collect::<Vec<_>>(), vec![1, 4, 4]);
+        assert_eq!(warehouse.step(Ortho::East), vec![2, 1]);
+        assert_eq!(warehouse.step(Ortho::East), vec![]);
+        assert_eq!(warehouse.draw(), "\
+########
+#......#
+#.O..bb#
+#..ccbb#
+#.@c...#
+#..c...#
+########
+");
+        assert_eq!(warehouse.gps(), 202 + 205 + 303);
+        warehouse.undo();
+        warehouse.undo();
+        assert_eq!(warehouse.robot(), (1, 4));
+        assert_eq!(warehouse.crates()[2].corner(), (2, 3));
+    }
+
+    #[test]
+    fn scaled_test() {
+        let (grid, start, moves) = input_generator(TEST2);
+        for scale in [(1, 1), (2, 1), (3, 1), (2, 2), (3, 4)] {
+            let mut warehouse = Warehouse::scaled(&grid, start, scale);
+            warehouse.run(&moves);
+            assert_eq!(warehouse.crates().len(), 21);
+            assert!(warehouse.crates().iter().all(|c| c.cells.len() == scale.0 * scale.1));
+        }
+    }
+
+    #[test]
+    fn undo_replay_test() {
+        let (grid, start, moves) = input_generator(TEST2);
+        let mut warehouse = Warehouse::widen(&grid, start);
+        let initial = warehouse.draw();
+        warehouse.run(&moves);
+        let last = warehouse.draw();
+
+        warehouse.replay(&moves, 300);
+        assert_eq!(warehouse.draw(), last);
+        assert_eq!(warehouse.history().len(), moves.len());
+        while warehouse.undo().is_some() {}
+        assert_eq!(warehouse.draw(), initial);
+        assert_eq!(warehouse.robot(), (start.0 * 2, start.1));
+    }
 }
\ No newline at end of file
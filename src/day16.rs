@@ -1,5 +1,5 @@
 #![allow(dead_code)]
-use std::usize;
+use std::ops::Range;
 use crate::prelude::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -10,14 +10,17 @@ pub enum Maze {
     Wall,
 }
 
+// A corridor between two nodes. The tiles it covers, both ends included, are a slice of the
+// graph's shared tile list.
 #[derive(Debug, Clone)]
 pub struct Edge {
     start: usize,
     start_dir: Ortho,
     end: usize,
     end_dir: Ortho,
-    path: HashSet<(usize, usize)>,
-    weight: usize,
+    path: Range<usize>,
+    steps: usize,
+    turns: usize,
 }
 
 impl Edge {
@@ -38,22 +41,66 @@ pub struct Node {
 
 impl Node {
     fn new(pos: &(usize, usize)) -> Self {
-        Node { pos: pos.clone(), exits: Vec::new() }
+        Node { pos: *pos, exits: Vec::new() }
     }
 
-    fn new_with_exits(pos: &(usize, usize), exits: &Vec<((usize, usize),Ortho)>) -> Self {
-        Node { pos: pos.clone(), exits: exits.clone() }
+    fn new_with_exits(pos: &(usize, usize), exits: &[((usize, usize), Ortho)]) -> Self {
+        Node { pos: *pos, exits: exits.to_vec() }
     }
 }
 
-fn get_node_num(node_list: &mut HashMap<usize, Node>, pos: &(usize, usize), neighbours: &Vec<((usize, usize), Ortho)>) -> usize {
-    if let Some((key, _)) = node_list.iter().find(|&(_, node)| node.pos == pos.clone()) {
-        return *key;
+// What moving around the maze costs. A U-turn of None means the reindeer can't turn
+// around on the spot at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostModel {
+    pub step: usize,
+    pub turn: usize,
+    pub u_turn: Option<usize>,
+    pub heading: Ortho,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self { step: 1, turn: 1000, u_turn: Some(2000), heading: Ortho::East }
+    }
+}
+
+impl CostModel {
+    fn turn_cost(&self, from: Ortho, to: Ortho) -> Option<usize> {
+        if from == to {
+            Some(0)
+        } else if from == to.flip() {
+            self.u_turn
+        } else {
+            Some(self.turn)
+        }
     }
 
-    let new_node = node_list.len();
-    node_list.insert(new_node, Node::new_with_exits(&pos, neighbours));
-    new_node
+    fn edge_cost(&self, edge: &Edge) -> usize {
+        edge.steps * self.step + edge.turns * self.turn
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub cost: usize,
+    pub nodes: Vec<usize>,
+    pub edges: Vec<usize>,
+    pub tiles: HashSet<(usize, usize)>,
+}
+
+// A node along with the heading the reindeer arrived in
+type Arrival = (usize, Ortho);
+
+fn slot((node, dir): Arrival) -> usize {
+    node * 4 + dir as usize
+}
+
+// Things a search has to avoid, for spurring off a known route
+#[derive(Default)]
+struct Bans {
+    nodes: HashSet<usize>,
+    edges: HashSet<(usize, usize)>,
 }
 
 #[derive(Eq, PartialEq)]
@@ -77,180 +124,212 @@ impl PartialOrd for State {
     }
 }
 
-fn reconstruct_paths(
-    end: usize,
-    entry_dir: Ortho,
-    previous: &HashMap<(usize, Ortho), Vec<(usize, Ortho)>>
-) -> Vec<Vec<usize>> {
-    let mut paths = Vec::new();
-    let mut stack = vec![vec![end]];
-    let mut current_states = vec![(end, entry_dir)];
-
-    while let Some(current_path) = stack.pop() {
-        let current_state = current_states.pop().unwrap();
-        
-        if let Some(prev_states) = previous.get(&current_state) {
-            if prev_states.is_empty() {
-                if current_path.len() > 1 {
-                    paths.push(current_path);
-                }
-            } else {
-                for &prev_state in prev_states {
-                    let mut new_path = current_path.clone();
-                    new_path.push(prev_state.0);
-                    stack.push(new_path);
-                    current_states.push(prev_state);
-                }
+pub struct Graph {
+    nodes: Vec<Node>,
+    index: HashMap<(usize, usize), usize>,
+    edges: Vec<Edge>,
+    adjacency: Vec<Vec<usize>>,
+    tiles: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    pub fn new(nodes: Vec<Node>, edges: Vec<Edge>, tiles: Vec<(usize, usize)>) -> Self {
+        let index = nodes.iter().enumerate().map(|(id, node)| (node.pos, id)).collect();
+        let mut adjacency = vec![Vec::new(); nodes.len()];
+        for (id, edge) in edges.iter().enumerate() {
+            adjacency[edge.start].push(id);
+            if edge.end != edge.start {
+                adjacency[edge.end].push(id);
             }
-        } else if current_path.len() > 1 {
-            paths.push(current_path);
         }
-    }
 
-    // Reverse all paths since we built them backwards
-    for path in &mut paths {
-        path.reverse();
+        Self { nodes, index, edges, adjacency, tiles }
     }
 
-    paths
-}
+    pub fn node_at(&self, pos: &(usize, usize)) -> Option<usize> {
+        self.index.get(pos).copied()
+    }
 
-pub struct Graph {
-    nodes: HashMap<usize, Node>,
-    edges: HashMap<(usize, usize, Ortho, Ortho), Edge>,
-}
+    pub fn edges_with_node(&self, node: usize) -> &[usize] {
+        &self.adjacency[node]
+    }
 
-impl Graph {
-    pub fn new(nodes: HashMap<usize, Node>, edges: HashMap<(usize, usize, Ortho, Ortho), Edge>) -> Self {
-        Self { nodes, edges }
+    pub fn edge_tiles(&self, edge: usize) -> &[(usize, usize)] {
+        &self.tiles[self.edges[edge].path.clone()]
     }
 
-    pub fn edges_with_node(&self, node: &usize) -> Vec<Edge> {
-        let mut edges = Vec::new();
+    // Leaves a node heading one way along an edge, returning where that ends up, the heading
+    // on arrival and what it cost including turning to face the edge
+    fn traverse(&self, node: usize, heading: Ortho, id: usize, cost: &CostModel) -> Option<(usize, Ortho, usize)> {
+        let edge = &self.edges[id];
+        let (launch_dir, exit_node, exit_dir) = if node == edge.start {
+            (edge.start_dir, edge.end, edge.end_dir.flip())
+        } else {
+            (edge.end_dir, edge.start, edge.start_dir.flip())
+        };
+        let turning_cost = cost.turn_cost(heading, launch_dir)?;
 
-        for ((key1, key2, _, _), edge) in self.edges.iter() {
-            if key1 == node || key2 == node {
-                edges.push(edge.clone());
-            }
-        }
-        edges
+        Some((exit_node, exit_dir, turning_cost + cost.edge_cost(edge)))
     }
 
-    pub fn get_edges(&self, node1: usize, node2: usize) -> Vec<Edge> {
-        let (key1, key2, _, _) = normalise_edge(node1, node2, Ortho::North, Ortho::North);
-        let mut results = Vec::new();
-        let mut min = usize::MAX;
-
-        for (&(first, second, _, _), edge) in self.edges.iter() {
-            if key1 == first && key2 == second {
-                if edge.weight < min {
-                    min = edge.weight;
-                    results.clear();
-                    results.push(edge.clone());
-                } else if edge.weight == min {
-                    results.push(edge.clone());
-                }
-            }
+    // Follows a list of edges from a starting node and heading
+    fn route(&self, start: usize, heading: Ortho, edges: Vec<usize>, cost: &CostModel) -> Route {
+        let (mut node, mut heading, mut total) = (start, heading, 0);
+        let mut nodes = vec![start];
+        let mut tiles = hashset!(self.nodes[start].pos);
+
+        for &id in &edges {
+            let (next, arrival, step_cost) = self.traverse(node, heading, id, cost).unwrap();
+            tiles.extend(self.edge_tiles(id).iter().copied());
+            nodes.push(next);
+            (node, heading) = (next, arrival);
+            total += step_cost;
         }
 
-        results
+        Route { cost: total, nodes, edges, tiles }
     }
 
-    pub fn dijkstra(&self, start: usize, end: usize, all_paths: bool) -> Option<Vec<(Vec<usize>, usize)>> {
-        let mut distances: HashMap<(usize, Ortho), usize> = HashMap::new();
-        let mut previous: HashMap<(usize, Ortho), Vec<(usize, Ortho)>> = HashMap::new();
+    // Dijkstra over (node, heading), returning the edges of every cheapest way to the end
+    // if all_paths is set, or just one of them otherwise
+    fn search(&self, from: Arrival, end: usize, cost: &CostModel, all_paths: bool, bans: &Bans) -> Vec<Vec<usize>> {
+        let states = self.nodes.len() * 4;
+        let mut distances = vec![usize::MAX; states];
+        let mut previous: Vec<Vec<(Arrival, usize)>> = vec![Vec::new(); states];
+        let mut visited = vec![false; states];
         let mut heap: BinaryHeap<State> = BinaryHeap::new();
-        let mut visited: HashSet<(usize, Ortho)> = HashSet::new();
-
-        for &id in self.nodes.keys() {
-            for dir in Ortho::iter() {
-                distances.insert((id, dir), usize::MAX);
-            }
-        }
 
-        distances.insert((start, Ortho::East), 0);
-        heap.push(State {
-            dist: 0,
-            node: start,
-            dir: Ortho::East,
-        });
+        distances[slot(from)] = 0;
+        heap.push(State { dist: 0, node: from.0, dir: from.1 });
 
         let mut shortest_dist = None;
-        let mut result_paths = Vec::new();
+        let mut ends = Vec::new();
 
         while let Some(State { dist: current_dist, node: current_node, dir: entry_dir }) = heap.pop() {
             let current_state = (current_node, entry_dir);
-            
-            if let Some(dist) = shortest_dist {
-                if current_dist > dist {
-                    continue;
-                }
-            }
 
-            if visited.contains(&current_state) {
+            if shortest_dist.is_some_and(|dist| current_dist > dist) {
+                break;
+            }
+            if visited[slot(current_state)] {
                 continue;
             }
-            visited.insert(current_state);
+            visited[slot(current_state)] = true;
 
             if current_node == end {
-                if shortest_dist.is_none() {
-                    shortest_dist = Some(current_dist);
-                }
-
-                let paths = reconstruct_paths(end, entry_dir, &previous);
-                let first_path = paths[0].clone();
+                shortest_dist = Some(current_dist);
+                ends.push(current_state);
                 if !all_paths {
-                    return Some(vec![(first_path, current_dist)]);
+                    break;
                 }
-                result_paths.extend(paths.into_iter().map(|path| (path, current_dist)));
                 continue;
             }
 
-            if current_dist > distances[&current_state] {
-                continue;
-            }
-
-            for next in self.edges_with_node(&current_node).iter() {
-                let from_start = current_node == next.start;
-                let launch_dir = if from_start { next.start_dir } else { next.end_dir };
-                if entry_dir == launch_dir.flip() {
+            for &id in self.edges_with_node(current_node) {
+                if bans.edges.contains(&(current_node, id)) {
                     continue;
                 }
-                let turning_cost = if entry_dir == launch_dir { 0 } else { 1000 };
-                let (exit_node, exit_dir) = if from_start {
-                    (next.end, next.end_dir.flip())
-                } else {
-                    (next.start, next.start_dir.flip())
+                let Some((exit_node, exit_dir, step_cost)) = self.traverse(current_node, entry_dir, id, cost) else {
+                    continue;
                 };
+                if bans.nodes.contains(&exit_node) {
+                    continue;
+                }
 
                 let next_state = (exit_node, exit_dir);
-                let new_dist = distances[&current_state]
-                    .saturating_add(1)
-                    .saturating_add(turning_cost)
-                    .saturating_add(next.weight);
-
-                if new_dist < distances[&next_state] {
-                    distances.insert(next_state, new_dist);
-                    previous.insert(next_state, vec![(current_node, entry_dir)]);
-                    heap.push(State {
-                        dist: new_dist,
-                        node: exit_node,
-                        dir: exit_dir,
-                    });
-                } else if new_dist == distances[&next_state] && all_paths {
-                    previous.entry(next_state)
-                        .or_default()
-                        .push((current_node, entry_dir));
+                let new_dist = current_dist + step_cost;
+                let best = distances[slot(next_state)];
+
+                if new_dist < best {
+                    distances[slot(next_state)] = new_dist;
+                    previous[slot(next_state)] = vec![(current_state, id)];
+                    heap.push(State { dist: new_dist, node: exit_node, dir: exit_dir });
+                } else if new_dist == best && all_paths {
+                    previous[slot(next_state)].push((current_state, id));
+                }
+            }
+        }
+
+        // Walk the predecessors back from each way of arriving at the end
+        let mut paths = Vec::new();
+        let mut stack: Vec<(Arrival, Vec<usize>)> = ends.into_iter().map(|state| (state, Vec::new())).collect();
+
+        while let Some((state, mut edges)) = stack.pop() {
+            if state == from {
+                edges.reverse();
+                paths.push(edges);
+                continue;
+            }
+            for &(prev_state, id) in &previous[slot(state)] {
+                let mut new_edges = edges.clone();
+                new_edges.push(id);
+                stack.push((prev_state, new_edges));
+                if !all_paths {
+                    break;
                 }
             }
         }
 
-        if result_paths.is_empty() {
+        paths
+    }
+
+    pub fn dijkstra(&self, start: usize, end: usize, cost: &CostModel, all_paths: bool) -> Option<Vec<Route>> {
+        let routes: Vec<Route> = self.search((start, cost.heading), end, cost, all_paths, &Bans::default())
+            .into_iter()
+            .map(|edges| self.route(start, cost.heading, edges, cost))
+            .collect();
+
+        if routes.is_empty() {
             None
         } else {
-            Some(result_paths)
+            Some(routes)
         }
     }
+
+    // The k cheapest distinct routes in order, using Yen's algorithm. Each new route spurs off
+    // a node of the last one found, keeping everything before it and banning whichever edges
+    // the routes found so far took next from there.
+    pub fn k_shortest(&self, start: usize, end: usize, cost: &CostModel, k: usize) -> Vec<Route> {
+        let mut found = match self.dijkstra(start, end, cost, false) {
+            Some(routes) if k > 0 => routes,
+            _ => return Vec::new(),
+        };
+        let mut candidates: Vec<Route> = Vec::new();
+
+        while found.len() < k {
+            let last = found.last().unwrap().clone();
+            let (mut node, mut heading) = (start, cost.heading);
+
+            for i in 0..last.edges.len() {
+                let root = &last.edges[..i];
+                let mut bans = Bans::default();
+                bans.nodes.extend(&last.nodes[..i]);
+                for route in &found {
+                    if route.edges.len() > i && route.edges[..i] == *root {
+                        bans.edges.insert((node, route.edges[i]));
+                    }
+                }
+
+                if let Some(spur) = self.search((node, heading), end, cost, false, &bans).pop() {
+                    let mut edges = root.to_vec();
+                    edges.extend(spur);
+                    let route = self.route(start, cost.heading, edges, cost);
+                    if !found.iter().chain(candidates.iter()).any(|known| known.edges == route.edges) {
+                        candidates.push(route);
+                    }
+                }
+
+                let (next, arrival, _) = self.traverse(node, heading, last.edges[i], cost).unwrap();
+                (node, heading) = (next, arrival);
+            }
+
+            let Some((best, _)) = candidates.iter().enumerate().min_by_key(|(_, route)| route.cost) else {
+                break;
+            };
+            found.push(candidates.swap_remove(best));
+        }
+
+        found
+    }
 }
 
 pub trait Neighbours {
@@ -287,87 +366,73 @@ where
     }
 }
 
-// For easy indexing, start node = 0, end node = 1
+// For easy indexing, start node = 0, end node = 1. Junctions are found in one pass over the
+// maze, then each corridor is walked once from whichever end gets to it first.
 fn build_graph(maze: &Grid<Maze>, start: (usize, usize), end: (usize, usize)) -> Graph {
-    let mut node_list = HashMap::new();
-    let mut edge_list = HashMap::new();
-    let mut queue = Vec::new();
-    let mut visited = HashSet::new();
-
-    let neighbours_start =  maze.get_neighbours(&start);
-    neighbours_start.iter().for_each(|(pos, dir)| queue.push((*pos, *dir, 0, hashset!(start.clone()), 0, dir.clone())));
-    visited.insert(start.clone());
-    node_list.insert(0, Node::new_with_exits(&start, &neighbours_start));
-
-    let neighbours_end =  maze.get_neighbours(&end);
-    visited.insert(end.clone());
-    node_list.insert(1, Node::new_with_exits(&end, &neighbours_end));
-
-    // Queue: (curr_pos: (usize, usize), curr_dir: Ortho, weight: usize, visited: HashSet<(usize, usize)>, origin: usize, start_dir: Ortho)
-    while let Some((curr_pos, curr_dir, weight, mut path, origin, start_dir)) = queue.pop() {
-        let neighbours = maze.get_neighbours(&curr_pos);
-
-        if curr_pos == end {
-            path.insert(curr_pos.clone());
-            let (start_node, end_node, start_direction, end_direction) = normalise_edge(origin, 1, start_dir, curr_dir.flip());
-            let edge = Edge {
-                start: start_node,
-                start_dir: start_direction,
-                end: end_node,
-                end_dir: end_direction,
-                path,
-                weight,
-            };
-            edge_list.insert((start_node, end_node, start_direction, end_direction), edge);
-            continue;
+    let mut nodes = vec![
+        Node::new_with_exits(&start, &maze.get_neighbours(&start)),
+        Node::new_with_exits(&end, &maze.get_neighbours(&end)),
+    ];
+    for y in 0..maze.height {
+        for x in 0..maze.width {
+            let pos = (x, y);
+            if maze[pos] != Maze::Wall && pos != start && pos != end {
+                let exits = maze.get_neighbours(&pos);
+                if exits.len() > 2 {
+                    nodes.push(Node::new_with_exits(&pos, &exits));
+                }
+            }
         }
+    }
+    let index: HashMap<(usize, usize), usize> = nodes.iter().enumerate().map(|(id, node)| (node.pos, id)).collect();
 
-        visited.insert(curr_pos);
-        if neighbours.len() > 2 { // Node
-            let node_idx = get_node_num(&mut node_list, &curr_pos, &neighbours);
-            path.insert(curr_pos.clone());
-            let (start_node, end_node, start_direction, end_direction) = normalise_edge(origin, node_idx, start_dir, curr_dir.flip());
-            let edge = Edge {
-                start: start_node,
-                start_dir: start_direction,
-                end: end_node,
-                end_dir: end_direction,
-                path,
-                weight,
-            };
-            let key = (start_node, end_node, start_direction, end_direction);
-            edge_list.entry(key).or_insert(edge);
+    let mut edges = Vec::new();
+    let mut tiles = Vec::new();
+    let mut launched = HashSet::new();
 
-            for (next_pos, next_dir) in neighbours {
-                if !visited.contains(&next_pos) {
-                    queue.push((next_pos, next_dir, 0, hashset!(curr_pos), node_idx, next_dir));
-                }
+    for (id, node) in nodes.iter().enumerate() {
+        for &(first, dir) in &node.exits {
+            if launched.contains(&(id, dir)) {
+                continue;
             }
-        } else { // Edge, dead ends die here
-            for (next_pos, next_dir) in neighbours {
-                let new_weight = if curr_dir == next_dir { weight + 1 } else { weight + 1001 };
-                path.insert(curr_pos.clone());
-                if next_dir != curr_dir.flip() {
-                    queue.push((next_pos, next_dir, new_weight, path.clone(), origin, start_dir));
+            launched.insert((id, dir));
+
+            let mark = tiles.len();
+            tiles.push(node.pos);
+            let (mut pos, mut heading, mut steps, mut turns) = (first, dir, 1, 0);
+
+            // Dead ends die here
+            loop {
+                tiles.push(pos);
+                if let Some(&other) = index.get(&pos) {
+                    launched.insert((other, heading.flip()));
+                    edges.push(Edge {
+                        start: id,
+                        start_dir: dir,
+                        end: other,
+                        end_dir: heading.flip(),
+                        path: mark..tiles.len(),
+                        steps,
+                        turns,
+                    });
+                    break;
+                }
+                let Some(&(next_pos, next_dir)) = maze.get_neighbours(&pos)
+                    .iter()
+                    .find(|(_, next_dir)| *next_dir != heading.flip()) else {
+                    tiles.truncate(mark);
+                    break;
+                };
+                if next_dir != heading {
+                    turns += 1;
                 }
+                (pos, heading) = (next_pos, next_dir);
+                steps += 1;
             }
         }
     }
 
-    Graph::new(node_list, edge_list)
-}
-
-fn normalise_edge(
-    node1: usize,
-    node2: usize,
-    dir1: Ortho,
-    dir2: Ortho,
-) -> (usize, usize, Ortho, Ortho) {
-    if node1 < node2 {
-        (node1, node2, dir1, dir2)
-    } else {
-        (node2, node1, dir2, dir1)
-    }
+    Graph::new(nodes, edges, tiles)
 }
 
 #[aoc_generator(day16)]
@@ -398,20 +463,16 @@ pub fn input_generator(input: &str) -> Graph {
 
 #[aoc(day16, part1)]
 pub fn solve_part1(graph: &Graph) -> usize {
-    graph.dijkstra(0, 1, false).unwrap()[0].1
+    graph.dijkstra(0, 1, &CostModel::default(), false).unwrap()[0].cost
 }
 
 #[aoc(day16, part2)]
 pub fn solve_part2(graph: &Graph) -> usize {
-    let paths = graph.dijkstra(0, 1, true).unwrap();
+    let routes = graph.dijkstra(0, 1, &CostModel::default(), true).unwrap();
     let mut all_paths = HashSet::new();
 
-    for (path, _) in paths {
-        for window in path.windows(2) {
-            for edge in graph.get_edges(window[0], window[1]) {
-                all_paths.extend(edge.path);
-            }
-        }
+    for route in routes {
+        all_paths.extend(route.tiles);
     }
 
     all_paths.len()
@@ -474,4 +535,63 @@ mod tests {
     fn part2_test2() {
         assert_eq!(solve_part2(&input_generator(TEST2)), 64);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn cost_model_test() {
+        let graph = input_generator(TEST1);
+        let cheapest = |cost: CostModel| graph.dijkstra(0, 1, &cost, false).unwrap()[0].cost;
+
+        let steps_only = CostModel { turn: 0, ..CostModel::default() };
+        let facing_north = CostModel { heading: Ortho::North, ..CostModel::default() };
+        // Facing south, the start only opens north or into a dead end to the east
+        let no_u_turns = CostModel { heading: Ortho::South, u_turn: None, ..CostModel::default() };
+        let cheap_u_turns = CostModel { heading: Ortho::South, u_turn: Some(500), ..CostModel::default() };
+        let pricey_steps = CostModel { step: 3, turn: 7, u_turn: Some(14), ..CostModel::default() };
+        assert_eq!(cheapest(steps_only), 28);
+        assert_eq!(cheapest(facing_north), 6036);
+        assert!(graph.dijkstra(0, 1, &no_u_turns, false).is_none());
+        assert_eq!(cheapest(cheap_u_turns), 6536);
+        assert_eq!(cheapest(pricey_steps), 154);
+    }
+
+    #[test]
+    fn k_shortest_test() {
+        let graph = input_generator(TEST1);
+        let cost = CostModel::default();
+        let routes = graph.k_shortest(0, 1, &cost, 10);
+        let costs: Vec<usize> = routes.iter().map(|route| route.cost).collect();
+        assert_eq!(costs, vec![7036, 7036, 7036, 9040, 10028, 10028, 10028, 11048, 12032, 12036]);
+
+        // The three tied routes are exactly the ones part 2 counts tiles over
+        let tiles: HashSet<_> = routes[..3].iter().flat_map(|route| route.tiles.iter().copied()).collect();
+        assert_eq!(tiles.len(), 45);
+        assert_eq!(graph.dijkstra(0, 1, &cost, true).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn large_maze_test() {
+        // Pillars on every even tile make a junction of every odd one
+        let size = 301;
+        let maze: String = (0..size)
+            .map(|y| (0..size).map(|x| match (x, y) {
+                (1, y) if y == size - 2 => 'S',
+                (x, 1) if x == size - 2 => 'E',
+                (x, y) if x == 0 || y == 0 || x == size - 1 || y == size - 1 => '#',
+                (x, y) if x % 2 == 0 && y % 2 == 0 => '#',
+                _ => '.',
+            }).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let graph = input_generator(&maze);
+
+        // Every odd tile but the two corners that are only bends
+        assert_eq!(graph.nodes.len(), 150 * 150 - 2);
+        assert_eq!(graph.node_at(&(1, 1)), None);
+        assert_eq!(graph.node_at(&(3, 1)), Some(2));
+        assert_eq!(graph.edges_with_node(graph.node_at(&(151, 151)).unwrap()).len(), 4);
+
+        // Straight along the bottom and up the right hand side is the only way with one turn
+        assert_eq!(solve_part1(&graph), 2 * (size - 3) + 1000);
+        assert_eq!(solve_part2(&graph), 2 * (size - 3) + 1);
+    }
+}
@@ -1,6 +1,34 @@
 use crate::prelude::*;
+use rayon::prelude::*;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
 
-struct Machine {
+// How many instructions a machine runs before giving up on it ever halting
+pub const DEFAULT_BUDGET: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmError {
+    InvalidCombo { ip: usize, operand: usize },
+    MisalignedJump { ip: usize, target: usize },
+    StepLimit(usize),
+    InvalidOpcode { ip: usize, opcode: usize },
+    Truncated { ip: usize },
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::InvalidCombo { ip, operand } => write!(f, "invalid combo operand {} at {}", operand, ip),
+            VmError::MisalignedJump { ip, target } => write!(f, "jump at {} to odd address {}", ip, target),
+            VmError::StepLimit(steps) => write!(f, "still running after {} instructions", steps),
+            VmError::InvalidOpcode { ip, opcode } => write!(f, "invalid opcode {} at {}", opcode, ip),
+            VmError::Truncated { ip } => write!(f, "instruction at {} is missing its operand", ip),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Machine {
     al: usize,
     bl: usize,
     cl: usize,
@@ -8,35 +36,79 @@ struct Machine {
     cs: Vec<usize>,
     ss: Vec<usize>,
     os: bool,
+    steps: usize,
+    budget: usize,
 }
 
 impl Machine {
     // *** All the basic shit ***
-    fn new(regs: &Vec<usize>, prog: &Vec<usize>) -> Self {
+    pub fn new(regs: &[usize], prog: &[usize]) -> Self {
         Self { 
             al: regs[0], bl: regs[1], cl: regs[2],
             ip: 0,
-            cs: prog.clone(),
+            cs: prog.to_vec(),
             ss: Vec::new(),
-            os: true,
+            os: !prog.is_empty(),
+            steps: 0,
+            budget: DEFAULT_BUDGET,
         }
     }
 
+    pub fn with_budget(self, budget: usize) -> Self {
+        Self { budget, ..self }
+    }
+
     // Run the damned machine
-    fn run(&mut self) {
+    pub fn run(&mut self) -> Result<(), VmError> {
         while self.os {
-            match self.get_opcode() {
-                0 => self.adv(),
-                1 => self.bxl(),
-                2 => self.bst(),
-                3 => self.jnz(),
-                4 => self.bxc(),
-                5 => self.out(),
-                6 => self.bdv(),
-                7 => self.cdv(),
-                _ => unreachable!(),
-            }
+            self.step()?;
         }
+
+        Ok(())
+    }
+
+    // Just the one instruction. A failed instruction leaves the machine as it was.
+    pub fn step(&mut self) -> Result<(), VmError> {
+        if self.steps >= self.budget {
+            return Err(VmError::StepLimit(self.steps));
+        }
+        if self.ip + 1 == self.cs.len() {
+            return Err(VmError::Truncated { ip: self.ip });
+        }
+        match self.get_opcode() {
+            0 => self.adv()?,
+            1 => self.bxl(),
+            2 => self.bst()?,
+            3 => self.jnz()?,
+            4 => self.bxc(),
+            5 => self.out()?,
+            6 => self.bdv()?,
+            7 => self.cdv()?,
+            opcode => return Err(VmError::InvalidOpcode { ip: self.ip, opcode }),
+        }
+        self.steps += 1;
+
+        Ok(())
+    }
+
+    pub fn registers(&self) -> [usize; 3] {
+        [self.al, self.bl, self.cl]
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn output(&self) -> &[usize] {
+        &self.ss
+    }
+
+    pub fn halted(&self) -> bool {
+        !self.os
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
     }
 
     // Get the opcode
@@ -49,37 +121,43 @@ impl Machine {
         self.cs[self.ip + 1]
     }
 
-    // Increment the instruction pointer
-    fn inc_ptr(&mut self) {
-        if self.ip < self.cs.len() - 2 {
-            self.ip += 2;
-        } else {
+    // Moves to the pointer, halting once it's past the end. Landing on a lone trailing opcode
+    // is left for the next step to fail on.
+    fn goto(&mut self, ip: usize) {
+        self.ip = ip;
+        if ip >= self.cs.len() {
             self.hcf();
         }
     }
 
+    // Increment the instruction pointer
+    fn inc_ptr(&mut self) {
+        self.goto(self.ip + 2);
+    }
+
     // Used only for combo operands
-    fn combo(&self, operand: usize) -> usize {
+    fn combo(&self, operand: usize) -> Result<usize, VmError> {
         match operand {
-            0..=3 => operand,
-            4 => self.al,
-            5 => self.bl,
-            6 => self.cl,
-            7 => unreachable!(),
-            _ => panic!(),
+            0..=3 => Ok(operand),
+            4 => Ok(self.al),
+            5 => Ok(self.bl),
+            6 => Ok(self.cl),
+            _ => Err(VmError::InvalidCombo { ip: self.ip, operand }),
         }
     }
 
+    // al shifted down by the combo operand, for all three of the division opcodes
+    fn dv(&self) -> Result<usize, VmError> {
+        let shift = self.combo(self.get_op())?;
+        Ok(if shift >= usize::BITS as usize { 0 } else { self.al >> shift })
+    }
+
     // Dump the contents of the Machine for study
-    #[allow(dead_code)]
-    fn core_dump(&self) {
-        println!("al: {}", self.al);
-        println!("bl: {}", self.bl);
-        println!("cl: {}", self.cl);
-        println!("ip: {}", self.ip);
-        println!("cs: {:?}", self.cs);
-        println!("ss: {:?}", self.ss);
-        println!("os: {}", self.os);
+    pub fn core_dump(&self) -> String {
+        format!(
+            "al: {}\nbl: {}\ncl: {}\nip: {}\ncs: {:?}\nss: {:?}\nos: {}\nsteps: {}/{}",
+            self.al, self.bl, self.cl, self.ip, self.cs, self.ss, self.os, self.steps, self.budget,
+        )
     }
 
     // Get the final solution
@@ -92,9 +170,10 @@ impl Machine {
 
     // *** All the opcode shit ***
     // Opcode 0 - divide al by combo operand
-    fn adv(&mut self,) {
-        self.al = self.al / 2_usize.pow(self.combo(self.get_op()) as u32);
+    fn adv(&mut self) -> Result<(), VmError> {
+        self.al = self.dv()?;
         self.inc_ptr();
+        Ok(())
     }
 
     // Opcode 1 - bitwise XOR of bl and literal operand
@@ -104,18 +183,23 @@ impl Machine {
     }
 
     // Opcode 2 - truncates the combo operand
-    fn bst(&mut self) {
-        self.bl = self.combo(self.get_op()) % 8;
+    fn bst(&mut self) -> Result<(), VmError> {
+        self.bl = self.combo(self.get_op())? % 8;
         self.inc_ptr();
+        Ok(())
     }
 
     // Opcode 3 - jump not zero; places pointer to literal operand if al not zero
-    fn jnz(&mut self) {
-        if self.al != 0 {
-            self.ip = self.get_op();
+    fn jnz(&mut self) -> Result<(), VmError> {
+        let target = self.get_op();
+        if self.al == 0 {
+            self.inc_ptr();
+        } else if target % 2 == 1 {
+            return Err(VmError::MisalignedJump { ip: self.ip, target });
         } else {
-            self.hcf();
+            self.goto(target);
         }
+        Ok(())
     }
 
     // Opcode 4 - bitwise XOR of bl and cl
@@ -125,21 +209,24 @@ impl Machine {
     }
 
     // Opcode 5 - outputs the combo operand after modulo
-    fn out(&mut self) {
-        self.ss.push(self.combo(self.get_op()) % 8);
+    fn out(&mut self) -> Result<(), VmError> {
+        self.ss.push(self.combo(self.get_op())? % 8);
         self.inc_ptr();
+        Ok(())
     }
 
     // Opcode 6 - divide al by combo operand but place in bl
-    fn bdv(&mut self,) {
-        self.bl = self.al / 2_usize.pow(self.combo(self.get_op()) as u32);
+    fn bdv(&mut self) -> Result<(), VmError> {
+        self.bl = self.dv()?;
         self.inc_ptr();
+        Ok(())
     }
 
     // Opcode 7 - divide al by combo operand but place in cl
-    fn cdv(&mut self,) {
-        self.cl = self.al / 2_usize.pow(self.combo(self.get_op()) as u32);
+    fn cdv(&mut self) -> Result<(), VmError> {
+        self.cl = self.dv()?;
         self.inc_ptr();
+        Ok(())
     }
 
     // Opcode X - stop everything
@@ -148,12 +235,487 @@ impl Machine {
     }
 }
 
+const MNEMONICS: [&str; 8] = ["adv", "bxl", "bst", "jnz", "bxc", "out", "bdv", "cdv"];
+
+// bxl and jnz take their operand as is, and bxc ignores it
+fn takes_combo(opcode: usize) -> bool {
+    matches!(opcode, 0 | 2 | 5 | 6 | 7)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AsmError {
+    UnknownMnemonic(usize, String),
+    BadOperand(usize, String),
+}
+
+// One instruction a line, like "adv 3" or "out b", with anything after a ';' ignored.
+// Combo operands can be 0-3 or a register name, literal ones 0-7. An instruction with no
+// operand assembles to just the opcode.
+pub fn assemble(source: &str) -> Result<Vec<usize>, AsmError> {
+    let mut prog = Vec::new();
+
+    for (line_no, line) in source.lines().enumerate() {
+        let line = line.split(';').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let mnemonic = parts.next().unwrap().to_lowercase();
+        let opcode = MNEMONICS.iter()
+            .position(|&name| name == mnemonic)
+            .ok_or(AsmError::UnknownMnemonic(line_no + 1, mnemonic))?;
+        prog.push(opcode);
+
+        let Some(operand) = parts.next() else {
+            continue;
+        };
+        let value = match operand.to_lowercase().as_str() {
+            "a" if takes_combo(opcode) => 4,
+            "b" if takes_combo(opcode) => 5,
+            "c" if takes_combo(opcode) => 6,
+            num => num.parse().ok().filter(|&num: &usize| num < 8)
+                .ok_or(AsmError::BadOperand(line_no + 1, operand.to_string()))?,
+        };
+        if parts.next().is_some() {
+            return Err(AsmError::BadOperand(line_no + 1, line.to_string()));
+        }
+        prog.push(value);
+    }
+
+    Ok(prog)
+}
+
+// The reverse of assemble, with combo operands naming their registers
+pub fn disassemble(prog: &[usize]) -> String {
+    prog.chunks(2)
+        .map(|instr| {
+            let mnemonic = MNEMONICS.get(instr[0]).copied().unwrap_or("???");
+            match instr.get(1) {
+                None => mnemonic.to_string(),
+                Some(&operand) => {
+                    let operand = match operand {
+                        4 if takes_combo(instr[0]) => "A".to_string(),
+                        5 if takes_combo(instr[0]) => "B".to_string(),
+                        6 if takes_combo(instr[0]) => "C".to_string(),
+                        _ => operand.to_string(),
+                    };
+                    format!("{} {}", mnemonic, operand)
+                },
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
 impl fmt::Debug for Machine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.ss.iter().map(|num| num.to_string()).collect::<Vec<String>>().join(","))
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    A,
+    B,
+    C,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    // About to run the instruction at this pointer
+    Ip(usize),
+    // This many values have been output
+    Outputs(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Halted,
+    Break(Breakpoint),
+    // Register, old value, new value
+    Watch(Register, usize, usize),
+    Fault(VmError),
+}
+
+// One executed instruction, with the registers as they were before it ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub step: usize,
+    pub ip: usize,
+    pub opcode: usize,
+    pub operand: usize,
+    pub regs: [usize; 3],
+    pub output: Option<usize>,
+}
+
+pub struct Debugger {
+    start: Machine,
+    machine: Machine,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<Register>,
+    trace: Vec<TraceEntry>,
+}
+
+impl Debugger {
+    pub fn new(machine: Machine) -> Self {
+        Self { start: machine.clone(), machine, breakpoints: Vec::new(), watches: Vec::new(), trace: Vec::new() }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    // Back to the beginning with a different A, keeping breakpoints and watches
+    pub fn reset(&mut self, al: usize) {
+        self.machine = Machine { al, ..self.start.clone() };
+        self.trace.clear();
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.retain(|&other| other != breakpoint);
+    }
+
+    pub fn watch(&mut self, register: Register) {
+        if !self.watches.contains(&register) {
+            self.watches.push(register);
+        }
+    }
+
+    pub fn unwatch(&mut self, register: Register) {
+        self.watches.retain(|&other| other != register);
+    }
+
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
+    // Runs one instruction, or nothing if the machine has already halted
+    pub fn step(&mut self) -> Result<Option<&TraceEntry>, VmError> {
+        if self.machine.halted() {
+            return Ok(None);
+        }
+        // The machine checks there's a whole instruction before anything reads the operand
+        let (ip, outputs, regs) = (self.machine.ip, self.machine.ss.len(), self.machine.registers());
+        self.machine.step()?;
+        self.trace.push(TraceEntry {
+            step: self.trace.len(),
+            ip,
+            opcode: self.machine.cs[ip],
+            operand: self.machine.cs[ip + 1],
+            regs,
+            output: (self.machine.ss.len() > outputs).then(|| *self.machine.ss.last().unwrap()),
+        });
+
+        Ok(self.trace.last())
+    }
+
+    // Keeps stepping until something worth stopping for happens
+    pub fn resume(&mut self) -> Stop {
+        loop {
+            let entry = match self.step() {
+                Ok(Some(entry)) => entry.clone(),
+                Ok(None) => break,
+                Err(err) => return Stop::Fault(err),
+            };
+            let before = entry.regs;
+            let after = self.machine.registers();
+            for &register in &self.watches {
+                let idx = register as usize;
+                if before[idx] != after[idx] {
+                    return Stop::Watch(register, before[idx], after[idx]);
+                }
+            }
+            if self.machine.halted() {
+                break;
+            }
+            for &breakpoint in &self.breakpoints {
+                let hit = match breakpoint {
+                    Breakpoint::Ip(ip) => self.machine.ip == ip,
+                    Breakpoint::Outputs(count) => entry.output.is_some() && self.machine.ss.len() == count,
+                };
+                if hit {
+                    return Stop::Break(breakpoint);
+                }
+            }
+        }
+
+        Stop::Halted
+    }
+
+    // The trace as CSV, one instruction a row
+    pub fn export_trace(&self) -> String {
+        let mut out = String::from("step,ip,instruction,a,b,c,output\n");
+        for entry in &self.trace {
+            out.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                entry.step, entry.ip, disassemble(&[entry.opcode, entry.operand]),
+                entry.regs[0], entry.regs[1], entry.regs[2],
+                entry.output.map(|num| num.to_string()).unwrap_or_default(),
+            ));
+        }
+
+        out
+    }
+}
+
+fn parse_register(name: &str) -> Option<Register> {
+    match name.to_lowercase().as_str() {
+        "a" => Some(Register::A),
+        "b" => Some(Register::B),
+        "c" => Some(Register::C),
+        _ => None,
+    }
+}
+
+// A line-at-a-time debugger over any reader and writer, so the runner can hook it up to a
+// terminal. Commands:
+//   s [n]           step n instructions
+//   c               continue to the next breakpoint, watchpoint or halt
+//   b ip|out <n>    add a breakpoint, d ip|out <n> removes it
+//   w <reg>         watch a register, u <reg> stops watching
+//   a <n>           restart with A set to n
+//   r               registers and output
+//   l               listing with the current instruction marked
+//   t               trace as CSV
+//   q               quit
+pub fn repl(regs: &[usize], prog: &[usize], input: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+    let mut debugger = Debugger::new(Machine::new(regs, prog));
+
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |idx: usize| words.get(idx).and_then(|word| word.parse::<usize>().ok());
+
+        match words.as_slice() {
+            [] => continue,
+            ["q"] => break,
+            ["s", ..] => {
+                for _ in 0..number(1).unwrap_or(1) {
+                    match debugger.step() {
+                        Ok(Some(entry)) => writeln!(out, "{:>3}: {}", entry.ip, disassemble(&[entry.opcode, entry.operand]))?,
+                        Ok(None) => {
+                            writeln!(out, "halted")?;
+                            break;
+                        },
+                        Err(err) => {
+                            writeln!(out, "error: {}", err)?;
+                            break;
+                        },
+                    }
+                }
+            },
+            ["c"] => match debugger.resume() {
+                Stop::Halted => writeln!(out, "halted")?,
+                Stop::Break(breakpoint) => writeln!(out, "break {:?}", breakpoint)?,
+                Stop::Watch(register, old, new) => writeln!(out, "watch {:?}: {} -> {}", register, old, new)?,
+                Stop::Fault(err) => writeln!(out, "error: {}", err)?,
+            },
+            [cmd @ ("b" | "d"), kind, _] => {
+                let breakpoint = match (*kind, number(2)) {
+                    ("ip", Some(ip)) => Breakpoint::Ip(ip),
+                    ("out", Some(count)) => Breakpoint::Outputs(count),
+                    _ => {
+                        writeln!(out, "?")?;
+                        continue;
+                    },
+                };
+                if *cmd == "b" {
+                    debugger.add_breakpoint(breakpoint);
+                } else {
+                    debugger.remove_breakpoint(breakpoint);
+                }
+            },
+            [cmd @ ("w" | "u"), name] => match parse_register(name) {
+                Some(register) if *cmd == "w" => debugger.watch(register),
+                Some(register) => debugger.unwatch(register),
+                None => writeln!(out, "?")?,
+            },
+            ["a", _] => match number(1) {
+                Some(al) => debugger.reset(al),
+                None => writeln!(out, "?")?,
+            },
+            ["r"] => {
+                let [al, bl, cl] = debugger.machine().registers();
+                writeln!(out, "A: {} B: {} C: {} ip: {} out: {}", al, bl, cl, debugger.machine().ip(), debugger.machine().ss_to_string())?;
+            },
+            ["l"] => {
+                for (idx, line) in disassemble(prog).lines().enumerate() {
+                    let marker = if idx * 2 == debugger.machine().ip() { ">" } else { " " };
+                    writeln!(out, "{} {:>3}: {}", marker, idx * 2, line)?;
+                }
+            },
+            ["t"] => write!(out, "{}", debugger.export_trace())?,
+            _ => writeln!(out, "?")?,
+        }
+    }
+
+    Ok(())
+}
+
+// What a compiled instruction tells the loop running it
+enum Flow {
+    Next,
+    Jump(usize),
+    Out(usize),
+    Fault(VmError),
+}
+
+type Op = Box<dyn Fn(&mut [usize; 3]) -> Flow + Send + Sync>;
+
+#[derive(Clone, Copy)]
+enum Combo {
+    Literal(usize),
+    Reg(usize),
+    Reserved(usize),
+}
+
+fn decode_combo(operand: usize) -> Combo {
+    match operand {
+        0..=3 => Combo::Literal(operand),
+        4..=6 => Combo::Reg(operand - 4),
+        _ => Combo::Reserved(operand),
+    }
+}
+
+// The program turned into one closure per instruction, with operands and jump targets
+// decoded up front. Anything the machine would fail on becomes a closure that faults the
+// same way when it's reached, so both agree on every program.
+pub struct Compiled {
+    ops: Vec<Op>,
+    bl: usize,
+    cl: usize,
+    budget: usize,
+}
+
+impl Compiled {
+    pub fn new(regs: &[usize], prog: &[usize]) -> Self {
+        let mut ops: Vec<Op> = prog.chunks_exact(2)
+            .enumerate()
+            .map(|(idx, instr)| Self::op(idx * 2, instr[0], instr[1]))
+            .collect();
+        if prog.len() % 2 == 1 {
+            let ip = prog.len() - 1;
+            ops.push(Box::new(move |_| Flow::Fault(VmError::Truncated { ip })));
+        }
+
+        Self { ops, bl: regs[1], cl: regs[2], budget: DEFAULT_BUDGET }
+    }
+
+    pub fn with_budget(self, budget: usize) -> Self {
+        Self { budget, ..self }
+    }
+
+    fn op(ip: usize, opcode: usize, operand: usize) -> Op {
+        let combo = decode_combo(operand);
+        if let (0 | 2 | 5 | 6 | 7, Combo::Reserved(operand)) = (opcode, combo) {
+            return Box::new(move |_| Flow::Fault(VmError::InvalidCombo { ip, operand }));
+        }
+
+        match (opcode, combo) {
+            (0 | 6 | 7, Combo::Literal(shift)) => {
+                let dst = if opcode == 0 { 0 } else { opcode - 5 };
+                Box::new(move |regs| {
+                    regs[dst] = regs[0] >> shift;
+                    Flow::Next
+                })
+            },
+            (0 | 6 | 7, Combo::Reg(src)) => {
+                let dst = if opcode == 0 { 0 } else { opcode - 5 };
+                Box::new(move |regs| {
+                    regs[dst] = regs[0].checked_shr(regs[src].try_into().unwrap_or(u32::MAX)).unwrap_or(0);
+                    Flow::Next
+                })
+            },
+            (1, _) => Box::new(move |regs| {
+                regs[1] ^= operand;
+                Flow::Next
+            }),
+            (2, Combo::Literal(value)) => Box::new(move |regs| {
+                regs[1] = value;
+                Flow::Next
+            }),
+            (2, Combo::Reg(src)) => Box::new(move |regs| {
+                regs[1] = regs[src] % 8;
+                Flow::Next
+            }),
+            (3, _) if operand % 2 == 1 => Box::new(move |regs| {
+                if regs[0] != 0 { Flow::Fault(VmError::MisalignedJump { ip, target: operand }) } else { Flow::Next }
+            }),
+            (3, _) => {
+                let target = operand / 2;
+                Box::new(move |regs| if regs[0] != 0 { Flow::Jump(target) } else { Flow::Next })
+            },
+            (4, _) => Box::new(|regs| {
+                regs[1] ^= regs[2];
+                Flow::Next
+            }),
+            (5, Combo::Literal(value)) => Box::new(move |_| Flow::Out(value)),
+            (5, Combo::Reg(src)) => Box::new(move |regs| Flow::Out(regs[src] % 8)),
+            _ => Box::new(move |_| Flow::Fault(VmError::InvalidOpcode { ip, opcode })),
+        }
+    }
+
+    // Runs with the given A, handing each output to `emit` until it returns false
+    fn execute(&self, al: usize, mut emit: impl FnMut(usize) -> bool) -> Result<(), VmError> {
+        let mut regs = [al, self.bl, self.cl];
+        let (mut idx, mut steps) = (0, 0);
+
+        while let Some(op) = self.ops.get(idx) {
+            if steps >= self.budget {
+                return Err(VmError::StepLimit(steps));
+            }
+            idx = match op(&mut regs) {
+                Flow::Next => idx + 1,
+                Flow::Jump(target) => target,
+                Flow::Out(value) => {
+                    if !emit(value) {
+                        return Ok(());
+                    }
+                    idx + 1
+                },
+                Flow::Fault(err) => return Err(err),
+            };
+            steps += 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn run(&self, al: usize) -> Result<Vec<usize>, VmError> {
+        let mut output = Vec::new();
+        self.execute(al, |value| {
+            output.push(value);
+            true
+        })?;
+
+        Ok(output)
+    }
+
+    // Bails out on the first output that doesn't match
+    pub fn outputs(&self, al: usize, target: &[usize]) -> bool {
+        let (mut matched, mut diverged) = (0, false);
+        let finished = self.execute(al, |value| {
+            diverged = target.get(matched) != Some(&value);
+            matched += 1;
+            !diverged
+        });
+
+        finished.is_ok() && !diverged && matched == target.len()
+    }
+}
+
+// The compiled program as a plain function of A
+pub fn compile(regs: &[usize], prog: &[usize]) -> impl Fn(usize) -> Result<Vec<usize>, VmError> + Send + Sync {
+    let compiled = Compiled::new(regs, prog);
+    move |al| compiled.run(al)
+}
+
 #[aoc_generator(day17)]
 pub fn input_generator(input: &str) -> (Vec<usize>, Vec<usize>) {
     let parts = input.split_once("\n\n").unwrap();
@@ -181,7 +743,7 @@ pub fn solve_part1((regs, prog): &(Vec<usize>, Vec<usize>)) -> String {
     // For testing internals
     // let mut cpu = Machine::new(&vec![0,2024,43690], &vec![4,0]);
     
-    cpu.run();
+    cpu.run().unwrap();
 
     // cpu.core_dump();
     cpu.ss_to_string()
@@ -211,9 +773,15 @@ pub fn solve_part2((_, prog): &(Vec<usize>, Vec<usize>)) -> usize {
     results[0]
 }
 
+#[cfg(feature = "z3")]
+#[aoc(day17, part2, Z3)]
+pub fn solve_part2_z3((regs, prog): &(Vec<usize>, Vec<usize>)) -> usize {
+    minimal_a(regs, prog, prog).unwrap()
+}
+
 fn search(al: usize, pos: usize, mini: &Vec<usize>, prog: &Vec<usize>, results: &mut Vec<usize>) {
-    let mut cpu = Machine::new(&vec![al, 0, 0], &mini);
-    cpu.run();
+    let mut cpu = Machine::new(&[al, 0, 0], mini);
+    cpu.run().unwrap();
 
     // Search in reverse as in testing
     if cpu.ss[0] != prog[(prog.len() - 1) - pos] {
@@ -229,6 +797,194 @@ fn search(al: usize, pos: usize, mini: &Vec<usize>, prog: &Vec<usize>, results:
     }
 }
 
+// Runs the program over a symbolic A as 64-bit vectors, splitting off a new path at every jnz
+// z3 can't decide, and keeps the paths that halt having output exactly the target. Nothing
+// about the shape of the program is assumed, so any loop structure or shift amount works.
+// Paths wait on a worklist rather than the call stack, and both how many jnz a path goes
+// through and how many paths get looked at are capped, so programs that spin forever on
+// some A just give up on those paths.
+#[cfg(feature = "z3")]
+mod symbolic {
+    use super::DEFAULT_BUDGET;
+    use z3::ast::{Ast, Bool, BV};
+    use z3::{Config, Context, SatResult, Solver};
+
+    const WIDTH: u32 = 64;
+    const MAX_FORKS: usize = 1024;
+    const MAX_PATHS: usize = 100_000;
+
+    // Where a path has got to, and everything that has to be true of A to get there
+    #[derive(Clone)]
+    struct Path<'ctx> {
+        ip: usize,
+        regs: [BV<'ctx>; 3],
+        facts: Vec<Bool<'ctx>>,
+        outputs: usize,
+        steps: usize,
+        forks: usize,
+    }
+
+    struct Search<'a, 'ctx> {
+        ctx: &'ctx Context,
+        solver: Solver<'ctx>,
+        prog: &'a [usize],
+        target: &'a [usize],
+        al: BV<'ctx>,
+        best: Option<u64>,
+    }
+
+    impl<'ctx> Search<'_, 'ctx> {
+        fn num(&self, num: usize) -> BV<'ctx> {
+            BV::from_u64(self.ctx, num as u64, WIDTH)
+        }
+
+        fn combo(&self, path: &Path<'ctx>, operand: usize) -> Option<BV<'ctx>> {
+            match operand {
+                0..=3 => Some(self.num(operand)),
+                4..=6 => Some(path.regs[operand - 4].clone()),
+                _ => None,
+            }
+        }
+
+        // Whether some A below the best so far fits what's asserted, plus the condition
+        fn feasible(&self, condition: Option<&Bool<'ctx>>) -> bool {
+            self.solver.push();
+            if let Some(condition) = condition {
+                self.solver.assert(condition);
+            }
+            if let Some(best) = self.best {
+                self.solver.assert(&self.al.bvult(&BV::from_u64(self.ctx, best, WIDTH)));
+            }
+            let sat = self.solver.check() == SatResult::Sat;
+            self.solver.pop(1);
+            sat
+        }
+
+        // Works through paths until they all halt, fail, or run out of forks
+        fn run(&mut self, start: Path<'ctx>) {
+            let mut work = vec![start];
+            let mut paths = 0;
+
+            while let Some(path) = work.pop() {
+                paths += 1;
+                if paths > MAX_PATHS {
+                    return;
+                }
+                self.solver.push();
+                for fact in &path.facts {
+                    self.solver.assert(fact);
+                }
+                if self.feasible(None) {
+                    self.explore(path, &mut work);
+                }
+                self.solver.pop(1);
+            }
+        }
+
+        // Steps along a path until it halts or does something invalid. Where a jnz could go
+        // either way the jump is left on the worklist and this carries on falling through.
+        fn explore(&mut self, mut path: Path<'ctx>, work: &mut Vec<Path<'ctx>>) {
+            while path.ip + 1 < self.prog.len() {
+                if path.steps >= DEFAULT_BUDGET {
+                    return;
+                }
+                path.steps += 1;
+
+                let (opcode, operand) = (self.prog[path.ip], self.prog[path.ip + 1]);
+                let combo = self.combo(&path, operand);
+                match (opcode, combo) {
+                    (0, Some(shift)) => path.regs[0] = path.regs[0].bvlshr(&shift),
+                    (1, _) => path.regs[1] = path.regs[1].bvxor(&self.num(operand)),
+                    (2, Some(value)) => path.regs[1] = value.bvand(&self.num(7)),
+                    (3, _) => {
+                        if path.forks >= MAX_FORKS {
+                            return;
+                        }
+                        path.forks += 1;
+
+                        // An odd target can't be jumped to, so only falling through survives
+                        let zero = path.regs[0]._eq(&self.num(0));
+                        let jumps = operand % 2 == 0;
+                        match (self.feasible(Some(&zero)), self.feasible(Some(&zero.not()))) {
+                            (true, true) => {
+                                if jumps {
+                                    let mut jump = Path { ip: operand, ..path.clone() };
+                                    jump.facts.push(zero.not());
+                                    work.push(jump);
+                                }
+                                self.solver.assert(&zero);
+                                path.facts.push(zero);
+                            },
+                            (true, false) => (),
+                            (false, true) if jumps => {
+                                path.ip = operand;
+                                continue;
+                            },
+                            _ => return,
+                        }
+                    },
+                    (4, _) => path.regs[1] = path.regs[1].bvxor(&path.regs[2]),
+                    (5, Some(value)) => {
+                        let Some(&expected) = self.target.get(path.outputs) else {
+                            return;
+                        };
+                        let fact = value.bvand(&self.num(7))._eq(&self.num(expected));
+                        self.solver.assert(&fact);
+                        path.facts.push(fact);
+                        path.outputs += 1;
+                    },
+                    (6, Some(shift)) => path.regs[1] = path.regs[0].bvlshr(&shift),
+                    (7, Some(shift)) => path.regs[2] = path.regs[0].bvlshr(&shift),
+                    _ => return,
+                }
+                path.ip += 2;
+            }
+
+            // Stopped on a lone opcode rather than running off the end
+            if path.ip < self.prog.len() {
+                return;
+            }
+            self.halt(&path);
+        }
+
+        // Pins down the smallest A for a finished path a bit at a time from the top
+        fn halt(&mut self, path: &Path<'ctx>) {
+            if path.outputs != self.target.len() || !self.feasible(None) {
+                return;
+            }
+            self.solver.push();
+            if let Some(best) = self.best {
+                self.solver.assert(&self.al.bvult(&BV::from_u64(self.ctx, best, WIDTH)));
+            }
+            for bit in (0..WIDTH).rev() {
+                let low = self.al.extract(bit, bit)._eq(&BV::from_u64(self.ctx, 0, 1));
+                let sat = self.feasible(Some(&low));
+                self.solver.assert(&if sat { low } else { low.not() });
+            }
+            self.solver.check();
+            self.best = self.solver.get_model()
+                .and_then(|model| model.eval(&self.al, true))
+                .and_then(|al| al.as_u64());
+            self.solver.pop(1);
+        }
+    }
+
+    // Smallest A that makes the program output the target, with B and C as given
+    pub fn minimal_a(regs: &[usize], prog: &[usize], target: &[usize]) -> Option<usize> {
+        let ctx = Context::new(&Config::new());
+        let al = BV::new_const(&ctx, "a", WIDTH);
+        let mut search = Search { ctx: &ctx, solver: Solver::new(&ctx), prog, target, al: al.clone(), best: None };
+
+        let regs = [al, search.num(regs[1]), search.num(regs[2])];
+        search.run(Path { ip: 0, regs, facts: Vec::new(), outputs: 0, steps: 0, forks: 0 });
+
+        search.best.map(|best| best as usize)
+    }
+}
+
+#[cfg(feature = "z3")]
+pub use symbolic::minimal_a;
+
 // Good for heating this winter... and the next ten
 // Test3 in reverse:
 //      0: 000
@@ -238,41 +994,11 @@ fn search(al: usize, pos: usize, mini: &Vec<usize>, prog: &Vec<usize>, results:
 //  14680: 011 100 101 011 000
 // 117440: 011 100 101 011 000 000
 // Looks like a possible 3-bit left shift solution (i.e. x8) and finding the next 3 bits
-// #[aoc(day17, part2, BruteForce)]
-// pub fn solve_part2((_, prog): &(Vec<usize>, Vec<usize>)) -> usize {
-//     let mut i = 0;
-
-//     'outer: loop {
-//         let mut cpu = Machine::new(&vec![i, 0, 0], prog);
-//         print!("\rIteration: {} ",i);
-
-//         while cpu.os {
-//             match cpu.get_opcode() {
-//                 0 => cpu.adv(),
-//                 1 => cpu.bxl(),
-//                 2 => cpu.bst(),
-//                 3 => cpu.jnz(),
-//                 4 => cpu.bxc(),
-//                 5 => cpu.out(),
-//                 6 => cpu.bdv(),
-//                 7 => cpu.cdv(),
-//                 _ => unreachable!(),
-//             }
-
-//             if cpu.ss.len() == cpu.cs.len() {
-//                 if cpu.ss == cpu.cs {
-//                     break 'outer;
-//                 } else {
-//                     break;
-//                 }
-//             }
-//         }
-
-//         i += 1;
-//     }
-
-//     i
-// }
+// Which it still is, but at least now it's compiled and uses every core
+pub fn brute_force(regs: &[usize], prog: &[usize], target: &[usize], range: Range<usize>) -> Option<usize> {
+    let compiled = Compiled::new(regs, prog);
+    range.into_par_iter().find_first(|&al| compiled.outputs(al, target))
+}
 
 #[cfg(test)]
 mod tests {
@@ -321,4 +1047,171 @@ Program: 0,3,5,4,3,0";
     fn part2_test() {
         assert_eq!(solve_part2(&input_generator(TEST4)), 117440);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn disassemble_test() {
+        let (_, prog) = input_generator(TEST4);
+        assert_eq!(disassemble(&prog), "adv 3\nout A\njnz 0");
+        assert_eq!(disassemble(&[4, 6, 1, 6, 2, 6, 2]), "bxc 6\nbxl 6\nbst C\nbst");
+        assert_eq!(assemble(&disassemble(&prog)), Ok(prog));
+    }
+
+    #[test]
+    fn assemble_test() {
+        let source = "bst a    ; last three bits of A
+            BXL 5
+            cdv B
+            bxc 0
+
+            adv 3
+            out b
+            jnz 0";
+        let prog = assemble(source).unwrap();
+        assert_eq!(prog, vec![2, 4, 1, 5, 7, 5, 4, 0, 0, 3, 5, 5, 3, 0]);
+        assert_eq!(assemble(&disassemble(&prog)), Ok(prog.clone()));
+
+        let mut cpu = Machine::new(&[2024, 0, 0], &prog);
+        cpu.run().unwrap();
+        assert_eq!(cpu.ss_to_string(), "2,5,5,6");
+
+        assert_eq!(assemble("mul 3"), Err(AsmError::UnknownMnemonic(1, "mul".to_string())));
+        assert_eq!(assemble("adv 3\nbxl a"), Err(AsmError::BadOperand(2, "a".to_string())));
+        assert_eq!(assemble("out 8"), Err(AsmError::BadOperand(1, "8".to_string())));
+    }
+
+    #[test]
+    fn debugger_test() {
+        let (regs, prog) = input_generator(TEST1);
+        let mut debugger = Debugger::new(Machine::new(&regs, &prog));
+
+        let first = debugger.step().unwrap().unwrap().clone();
+        assert_eq!((first.ip, first.opcode, first.operand, first.regs), (0, 0, 1, [729, 0, 0]));
+        assert_eq!(debugger.machine().registers(), [364, 0, 0]);
+
+        debugger.add_breakpoint(Breakpoint::Outputs(3));
+        assert_eq!(debugger.resume(), Stop::Break(Breakpoint::Outputs(3)));
+        assert_eq!(debugger.machine().output(), &[4, 6, 3]);
+
+        debugger.remove_breakpoint(Breakpoint::Outputs(3));
+        debugger.add_breakpoint(Breakpoint::Ip(4));
+        assert_eq!(debugger.resume(), Stop::Break(Breakpoint::Ip(4)));
+        debugger.remove_breakpoint(Breakpoint::Ip(4));
+        debugger.watch(Register::B);
+        assert_eq!(debugger.resume(), Stop::Halted);
+        assert_eq!(debugger.machine().ss_to_string(), "4,6,3,5,6,3,5,2,1,0");
+        assert_eq!(debugger.trace().len(), 30);
+
+        debugger.reset(2024);
+        debugger.watch(Register::A);
+        assert_eq!(debugger.resume(), Stop::Watch(Register::A, 2024, 1012));
+        assert_eq!(debugger.export_trace(), "step,ip,instruction,a,b,c,output\n0,0,adv 1,2024,0,0,\n");
+
+        // A lone opcode at the end is an error, not something to read past
+        let mut debugger = Debugger::new(Machine::new(&[0, 0, 0], &[5]));
+        assert_eq!(debugger.step().err(), Some(VmError::Truncated { ip: 0 }));
+        assert!(debugger.trace().is_empty());
+        let mut debugger = Debugger::new(Machine::new(&[0, 0, 0], &[3, 0, 5]));
+        assert_eq!(debugger.resume(), Stop::Fault(VmError::Truncated { ip: 2 }));
+        assert_eq!(debugger.trace().len(), 1);
+    }
+
+    #[test]
+    fn repl_test() {
+        let (regs, prog) = input_generator(TEST1);
+        let mut out = Vec::new();
+        repl(&regs, &prog, "b out 2\nc\nr\ns 2\nl\nx\nq\nr".as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+break Outputs(2)
+A: 182 B: 0 C: 0 ip: 4 out: 4,6
+  4: jnz 0
+  0: adv 1
+    0: adv 1
+>   2: out A
+    4: jnz 0
+?
+");
+
+        let mut out = Vec::new();
+        repl(&[0, 0, 0], &[5], "s\nc\nq".as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+error: instruction at 0 is missing its operand
+error: instruction at 0 is missing its operand
+");
+    }
+
+    #[test]
+    fn vm_error_test() {
+        let run = |al: usize, prog: Vec<usize>, budget: usize| {
+            let mut cpu = Machine::new(&[al, 0, 0], &prog).with_budget(budget);
+            cpu.run().map(|_| cpu.ss_to_string())
+        };
+
+        assert_eq!(run(1, vec![5, 7], 10), Err(VmError::InvalidCombo { ip: 0, operand: 7 }));
+        assert_eq!(run(1, vec![1, 2, 3, 1], 10), Err(VmError::MisalignedJump { ip: 2, target: 1 }));
+        assert_eq!(run(1, vec![3, 0], 10), Err(VmError::StepLimit(10)));
+        assert_eq!(run(1, vec![5, 4, 8, 0], 10), Err(VmError::InvalidOpcode { ip: 2, opcode: 8 }));
+
+        // Falling through a jnz onto a lone opcode, and shifting right out of range
+        assert_eq!(run(0, vec![3, 0, 5, 4, 5], 10), Err(VmError::Truncated { ip: 4 }));
+        assert_eq!(run(0, vec![5], 10), Err(VmError::Truncated { ip: 0 }));
+        assert_eq!(run(0, vec![3, 0, 5, 4], 10), Ok("0".to_string()));
+        assert_eq!(run(99, assemble("bst 7\nbxl 7\nadv 3\nout a").unwrap(), 10), Err(VmError::InvalidCombo { ip: 0, operand: 7 }));
+        assert_eq!(run(usize::MAX, vec![1, 100, 6, 5, 5, 5], 10), Ok("0".to_string()));
+    }
+
+    #[cfg(feature = "z3")]
+    #[test]
+    fn symbolic_test() {
+        let (regs, prog) = input_generator(TEST4);
+        assert_eq!(solve_part2_z3(&(regs.clone(), prog.clone())), 117440);
+
+        // The lowest bit of A never reaches the output
+        let (regs, prog) = input_generator(TEST1);
+        assert_eq!(minimal_a(&regs, &prog, &[4, 6, 3, 5, 6, 3, 5, 2, 1, 0]), Some(728));
+
+        // No adv 3 in sight, and nothing smaller does it
+        let prog = assemble("bst a\nbxl 5\ncdv b\nbxc 0\nadv 2\nout b\njnz 0").unwrap();
+        let run = |al: usize| {
+            let mut cpu = Machine::new(&[al, 0, 0], &prog);
+            cpu.run().unwrap();
+            cpu.ss_to_string()
+        };
+        let target = run(2024);
+        let target_nums: Vec<usize> = target.split(',').map(|num| num.parse().unwrap()).collect();
+        let al = minimal_a(&[0, 0, 0], &prog, &target_nums).unwrap();
+        assert!(al <= 2024);
+        assert_eq!(run(al), target);
+        assert!((0..al).all(|al| run(al) != target));
+        assert_eq!(minimal_a(&[0, 0, 0], &prog, &[7; 40]), None);
+
+        // Spins forever without output on any A but 0, and past the target on the rest
+        assert_eq!(minimal_a(&[0, 0, 0], &[3, 0], &[]), Some(0));
+        assert_eq!(minimal_a(&[0, 0, 0], &assemble("bxl 1\njnz 0").unwrap(), &[1]), None);
+        assert_eq!(minimal_a(&[0, 0, 0], &assemble("out a\njnz 0").unwrap(), &[3]), None);
+    }
+
+    #[test]
+    fn compiled_test() {
+        let (regs, prog) = input_generator(TEST1);
+        let run = compile(&regs, &prog);
+        assert_eq!(run(729), Ok(vec![4, 6, 3, 5, 6, 3, 5, 2, 1, 0]));
+
+        let (regs, prog) = input_generator(TEST4);
+        assert_eq!(brute_force(&regs, &prog, &prog, 0..200_000), Some(117440));
+        assert_eq!(brute_force(&regs, &prog, &prog, 0..100_000), None);
+
+        // Random programs, valid or not, go the same way compiled as interpreted
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(17);
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..12);
+            let prog: Vec<usize> = (0..len).map(|_| rng.gen_range(0..9)).collect();
+            let regs = vec![rng.gen_range(0..1 << 20), rng.gen_range(0..80), rng.gen_range(0..8)];
+
+            let mut cpu = Machine::new(&regs, &prog).with_budget(500);
+            let interpreted = cpu.run().map(|_| cpu.output().to_vec());
+            let compiled = Compiled::new(&regs, &prog).with_budget(500).run(regs[0]);
+            assert_eq!(interpreted, compiled, "{:?} {:?}", prog, regs);
+        }
+    }
+}
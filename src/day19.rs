@@ -1,66 +1,238 @@
 use crate::prelude::*;
-// use trie_rs::{map::Trie, map::TrieBuilder, inc_search::Answer};
+use rand::Rng;
 
-// Pretty similar to what I was trying to do with the trie, minus the trie
-fn search_pattern<'a>(pattern: &'a str, valid: &Vec<&'a str>, cache: &mut HashMap<&'a str, usize>, max_len: usize) -> usize {
-    let mut combos = 0;
-    if pattern.is_empty() {
-        return 1;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Onsen {
+    pub towels: Vec<String>,
+    pub designs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TrieNode {
+    next: HashMap<u8, usize>,
+    towel: Option<usize>,
+}
+
+// All the towels hung off a prefix trie, so finding every towel that fits at some point in a
+// design is one walk down the trie instead of checking every prefix against the whole list
+#[derive(Debug, Clone)]
+pub struct TowelTrie {
+    towels: Vec<String>,
+    nodes: Vec<TrieNode>,
+}
+
+impl TowelTrie {
+    pub fn new(towels: &[String]) -> Self {
+        let mut nodes = vec![TrieNode::default()];
+
+        for (id, towel) in towels.iter().enumerate() {
+            if towel.is_empty() {
+                continue;
+            }
+            let mut node = 0;
+            for &stripe in towel.as_bytes() {
+                node = match nodes[node].next.get(&stripe) {
+                    Some(&next) => next,
+                    None => {
+                        nodes.push(TrieNode::default());
+                        let next = nodes.len() - 1;
+                        nodes[node].next.insert(stripe, next);
+                        next
+                    }
+                };
+            }
+            // A repeated towel doesn't add any new ways to make a design
+            nodes[node].towel.get_or_insert(id);
+        }
+
+        Self { towels: towels.to_vec(), nodes }
     }
-    if cache.contains_key(pattern) {
-        return *cache.get(pattern).unwrap();
+
+    pub fn towel(&self, id: usize) -> &str {
+        &self.towels[id]
+    }
+
+    // Every (towel, end) where a towel starts at `start` in the design and finishes at `end`
+    pub fn matches<'a>(&'a self, design: &'a [u8], start: usize) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let mut node = Some(0);
+        design[start..].iter().enumerate().map_while(move |(i, stripe)| {
+            node = self.nodes[node?].next.get(stripe).copied();
+            Some((node?, start + i + 1))
+        }).filter_map(|(node, end)| Some((self.nodes[node].towel?, end)))
+    }
+
+    pub fn arrangements<'a>(&'a self, design: &'a str) -> Arrangements<'a> {
+        Arrangements::new(self, design)
+    }
+}
+
+// The DP over a single design, worked backwards so `ways[i]` is how many ways the design can
+// be finished from position i and `fewest[i]` is the fewest towels that'll do it
+#[derive(Debug, Clone)]
+pub struct Arrangements<'a> {
+    trie: &'a TowelTrie,
+    design: &'a str,
+    ways: Vec<usize>,
+    fewest: Vec<Option<usize>>,
+}
+
+impl<'a> Arrangements<'a> {
+    fn new(trie: &'a TowelTrie, design: &'a str) -> Self {
+        let bytes = design.as_bytes();
+        let mut ways = vec![0; bytes.len() + 1];
+        let mut fewest = vec![None; bytes.len() + 1];
+        ways[bytes.len()] = 1;
+        fewest[bytes.len()] = Some(0);
+
+        for start in (0..bytes.len()).rev() {
+            for (_, end) in trie.matches(bytes, start) {
+                ways[start] += ways[end];
+                if let Some(rest) = fewest[end] {
+                    fewest[start] = Some(fewest[start].map_or(rest + 1, |best: usize| best.min(rest + 1)));
+                }
+            }
+        }
+
+        Self { trie, design, ways, fewest }
+    }
+
+    pub fn possible(&self) -> bool {
+        self.ways[0] > 0
+    }
+
+    pub fn count(&self) -> usize {
+        self.ways[0]
+    }
+
+    // The arrangement using the fewest towels, if there's any at all
+    pub fn minimum(&self) -> Option<Vec<&'a str>> {
+        self.fewest[0]?;
+        let bytes = self.design.as_bytes();
+        let mut arrangement = Vec::new();
+        let mut pos = 0;
+
+        while pos < bytes.len() {
+            let (towel, end) = self.trie.matches(bytes, pos)
+                .find(|&(_, end)| self.fewest[end].is_some_and(|rest| rest + 1 == self.fewest[pos].unwrap()))
+                .unwrap();
+            arrangement.push(self.trie.towel(towel));
+            pos = end;
+        }
+
+        Some(arrangement)
+    }
+
+    // Every arrangement one at a time, only ever stepping onto positions that can still be
+    // finished so there's no backtracking out of dead ends
+    pub fn iter(&self) -> Decompositions<'_, 'a> {
+        let empty = self.design.is_empty();
+        let stack = if self.possible() && !empty { vec![self.choices(0)] } else { Vec::new() };
+        Decompositions { arrangements: self, stack, current: Vec::new(), empty }
     }
-    
 
-    for i in 1..=max_len.min(pattern.len()) {
-        if valid.contains(&&pattern[..i]) {
-            let subcount = search_pattern(&pattern[i..], valid, cache, max_len);
-            combos += subcount;
+    // Picks one arrangement uniformly at random out of all of them
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<Vec<&'a str>> {
+        if !self.possible() {
+            return None;
         }
+        let bytes = self.design.as_bytes();
+        let mut arrangement = Vec::new();
+        let mut pos = 0;
+
+        while pos < bytes.len() {
+            // Each towel gets picked in proportion to how many ways there are to finish after it
+            let mut pick = rng.gen_range(0..self.ways[pos]);
+            for (towel, end) in self.trie.matches(bytes, pos) {
+                if pick < self.ways[end] {
+                    arrangement.push(self.trie.towel(towel));
+                    pos = end;
+                    break;
+                }
+                pick -= self.ways[end];
+            }
+        }
+
+        Some(arrangement)
+    }
+
+    fn choices(&self, pos: usize) -> Vec<(usize, usize)> {
+        let mut choices: Vec<_> = self.trie.matches(self.design.as_bytes(), pos)
+            .filter(|&(_, end)| self.ways[end] > 0)
+            .collect();
+        choices.reverse();
+        choices
     }
-    cache.insert(pattern, combos);
-    combos
 }
 
-// #[aoc_generator(day19)]
-// pub fn input_generator(input: &str) -> (Vec<String>, Vec<String>) {
-//     let (towels, patterns) = input.split_once("\n\n").unwrap();
+pub struct Decompositions<'s, 'a> {
+    arrangements: &'s Arrangements<'a>,
+    stack: Vec<Vec<(usize, usize)>>,
+    current: Vec<usize>,
+    empty: bool,
+}
+
+impl<'a> Iterator for Decompositions<'_, 'a> {
+    type Item = Vec<&'a str>;
 
-//     (
-//         towels.split(", ").map(|part| part.to_string()).collect(),
-//         patterns.lines().map(|line| line.to_string()).collect(),
-//     )
-// }
+    fn next(&mut self) -> Option<Self::Item> {
+        let trie = self.arrangements.trie;
+        let len = self.arrangements.design.len();
+
+        // An empty design has exactly one arrangement, using no towels at all
+        if self.empty {
+            self.empty = false;
+            return Some(Vec::new());
+        }
 
-fn parse_input<'a>(input: &'a str) -> (Vec<&'a str>, Vec<&'a str>) {
-    let (towels, patterns) = input.split_once("\n\n").unwrap();
-    
-    (towels.split(", ").collect(), patterns.lines().collect())
+        while let Some(choices) = self.stack.last_mut() {
+            let Some((towel, end)) = choices.pop() else {
+                self.stack.pop();
+                self.current.pop();
+                continue;
+            };
+            self.current.push(towel);
+
+            if end == len {
+                let arrangement = self.current.iter().map(|&id| trie.towel(id)).collect();
+                self.current.pop();
+                return Some(arrangement);
+            }
+            let next = self.arrangements.choices(end);
+            self.stack.push(next);
+        }
+
+        None
+    }
+}
+
+#[aoc_generator(day19)]
+pub fn input_generator(input: &str) -> Onsen {
+    let (towels, designs) = input.split_once("\n\n").unwrap();
+
+    Onsen {
+        towels: towels.split(", ").map(|towel| towel.to_string()).collect(),
+        designs: designs.lines().map(|line| line.to_string()).collect(),
+    }
 }
 
-// Can't use lifetimes across aoc macros
 #[aoc(day19, part1)]
-pub fn part1<'a>(input: &str) -> usize {
-    let (towels, patterns) = parse_input(input);
-    let mut cache = HashMap::new();
-    let max_len = towels.iter().map(|towel| towel.len()).max().unwrap_or(0);
+pub fn part1(onsen: &Onsen) -> usize {
+    let trie = TowelTrie::new(&onsen.towels);
 
-    patterns
+    onsen.designs
         .iter()
-        .filter(|pattern| search_pattern(pattern, &towels, &mut cache, max_len) > 0)
+        .filter(|design| trie.arrangements(design).possible())
         .count()
 }
 
 // Test OK, 509085811057 too low
 #[aoc(day19, part2)]
-pub fn part2<'a>(input: &str) -> usize {
-    let (towels, patterns) = parse_input(input);
-    let mut cache = HashMap::new();
-    let max_len = towels.iter().map(|towel| towel.len()).max().unwrap_or(0);
-
-    patterns.iter()
-        .map(|pattern| search_pattern(pattern, &towels, &mut cache, max_len))
-        .filter(|&n| n > 0)
+pub fn part2(onsen: &Onsen) -> usize {
+    let trie = TowelTrie::new(&onsen.towels);
+
+    onsen.designs
+        .iter()
+        .map(|design| trie.arrangements(design).count())
         .sum()
 }
 
@@ -83,11 +255,56 @@ bbrgwb";
 
     #[test]
     fn part1_test() {
-        assert_eq!(part1(&TEST), 6);
+        assert_eq!(part1(&input_generator(TEST)), 6);
     }
 
     #[test]
     fn part2_test() {
-        assert_eq!(part2(&TEST), 16);
+        assert_eq!(part2(&input_generator(TEST)), 16);
+    }
+
+    #[test]
+    fn arrangements_test() {
+        let onsen = input_generator(TEST);
+        let trie = TowelTrie::new(&onsen.towels);
+
+        let gbbr = trie.arrangements("gbbr");
+        let mut all: Vec<_> = gbbr.iter().map(|towels| towels.join(",")).collect();
+        all.sort();
+        assert_eq!(all, ["g,b,b,r", "g,b,br", "gb,b,r", "gb,br"]);
+        assert_eq!(gbbr.minimum(), Some(vec!["gb", "br"]));
+
+        let rrbgbr = trie.arrangements("rrbgbr");
+        assert_eq!(rrbgbr.count(), 6);
+        assert_eq!(rrbgbr.minimum().map(|towels| towels.len()), Some(4));
+
+        for design in &onsen.designs {
+            let arrangements = trie.arrangements(design);
+            let all: HashSet<_> = arrangements.iter().collect();
+            assert_eq!(all.len(), arrangements.count());
+            assert!(all.iter().all(|towels| towels.concat() == *design));
+            assert_eq!(arrangements.minimum().is_some(), arrangements.possible());
+        }
+        assert_eq!(trie.arrangements("ubwu").iter().next(), None);
+        assert_eq!(trie.arrangements("").iter().collect::<Vec<_>>(), vec![Vec::<&str>::new()]);
+    }
+
+    #[test]
+    fn sample_test() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let onsen = input_generator(TEST);
+        let trie = TowelTrie::new(&onsen.towels);
+        let mut rng = StdRng::seed_from_u64(19);
+
+        let arrangements = trie.arrangements("gbbr");
+        let mut seen = HashSet::new();
+        for _ in 0..200 {
+            let towels = arrangements.sample(&mut rng).unwrap();
+            assert_eq!(towels.concat(), "gbbr");
+            seen.insert(towels);
+        }
+        assert_eq!(seen.len(), 4);
+        assert_eq!(trie.arrangements("bbrgwb").sample(&mut rng), None);
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use rayon::prelude::*;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Track {
@@ -7,18 +8,148 @@ pub enum Track {
     Wall,
 }
 
+// The racetrack, plus the least a cheat has to save before it's worth counting
+pub struct RaceTrack {
+    pub grid: Grid<Track>,
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub threshold: usize,
+}
+
+impl RaceTrack {
+    pub fn new(grid: Grid<Track>, start: (usize, usize), end: (usize, usize)) -> Self {
+        Self { grid, start, end, threshold: 100 }
+    }
+
+    pub fn with_threshold(self, threshold: usize) -> Self {
+        Self { threshold, ..self }
+    }
+
+    // Shortest honest distance from `from` to every tile, None for walls and anywhere cut off
+    pub fn distances(&self, from: (usize, usize)) -> Grid<Option<usize>> {
+        let mut dist = Grid::new_fill(self.grid.width, self.grid.height, None);
+        let mut queue = VecDeque::from([from]);
+        dist[from] = Some(0);
+
+        while let Some(pos) = queue.pop_front() {
+            let steps = dist[pos].unwrap();
+            for next in self.grid.neighbours_as(&pos, Track::Path) {
+                if dist[next].is_none() {
+                    dist[next] = Some(steps + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        dist
+    }
+
+    // The track can branch, loop back or dead end, so progress along it is measured with a
+    // BFS out from each end rather than the order tiles turn up in
+    pub fn analyse(&self) -> RaceAnalysis {
+        let from_start = self.distances(self.start);
+        let to_end = self.distances(self.end);
+        let best = from_start[self.end];
+
+        RaceAnalysis { from_start, to_end, best }
+    }
+}
+
+// How long a cheat-free race takes from the start to every tile, and from every tile to the end
+pub struct RaceAnalysis {
+    pub from_start: Grid<Option<usize>>,
+    pub to_end: Grid<Option<usize>>,
+    pub best: Option<usize>,
+}
+
+impl RaceAnalysis {
+    // Time saved by every cheat that starts at `pos` and lasts up to `radius` picoseconds. A
+    // cheat is only worth anything if running to its start, through the wall and on to the
+    // end beats the best honest time. If there's no honest way to the end at all there's
+    // nothing to compare against, so nothing counts.
+    pub fn savings_from(&self, pos: (usize, usize), radius: usize) -> impl Iterator<Item = usize> + '_ {
+        let (width, height) = (self.from_start.width, self.from_start.height);
+        let before = self.from_start[pos];
+        let radius = radius as i32;
+
+        (-radius..=radius).flat_map(move |dy| {
+            let reach = radius - dy.abs();
+            (-reach..=reach).map(move |dx| (dx, dy))
+        }).filter_map(move |(dx, dy)| {
+            let x = pos.0.checked_add_signed(dx as isize).filter(|&x| x < width)?;
+            let y = pos.1.checked_add_signed(dy as isize).filter(|&y| y < height)?;
+            let taken = before? + (dx.abs() + dy.abs()) as usize + self.to_end[(x, y)]?;
+            self.best?.checked_sub(taken).filter(|&saved| saved > 0)
+        })
+    }
+
+    fn tiles(&self) -> impl ParallelIterator<Item = (usize, usize)> + '_ {
+        let width = self.from_start.width;
+        (0..self.from_start.height).into_par_iter()
+            .flat_map_iter(move |y| (0..width).map(move |x| (x, y)))
+            .filter(|&pos| self.from_start[pos].is_some())
+    }
+
+    // How many cheats of up to `radius` save at least `threshold`
+    pub fn count(&self, radius: usize, threshold: usize) -> usize {
+        self.tiles()
+            .map(|pos| self.savings_from(pos, radius).filter(|&saved| saved >= threshold).count())
+            .sum()
+    }
+
+    // Every cheat of up to `radius` that saves at least `threshold`, tallied by what it saves
+    pub fn histogram(&self, radius: usize, threshold: usize) -> Savings {
+        let tally = self.tiles()
+            .fold(BTreeMap::new, |mut tally, pos| {
+                for saved in self.savings_from(pos, radius).filter(|&saved| saved >= threshold) {
+                    *tally.entry(saved).or_insert(0) += 1;
+                }
+                tally
+            })
+            .reduce(BTreeMap::new, |mut tally, other| {
+                for (saved, count) in other {
+                    *tally.entry(saved).or_insert(0) += count;
+                }
+                tally
+            });
+
+        Savings(tally)
+    }
+}
+
+// Number of cheats for each amount of time saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Savings(pub BTreeMap<usize, usize>);
+
+impl Savings {
+    pub fn total(&self) -> usize {
+        self.0.values().sum()
+    }
+}
+
+impl fmt::Display for Savings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (saved, count) in &self.0 {
+            let (is, cheats, save) = if *count == 1 { ("is", "cheat", "saves") } else { ("are", "cheats", "save") };
+            writeln!(f, "There {is} {count} {cheats} that {save} {saved} picoseconds.")?;
+        }
+        Ok(())
+    }
+}
+
 #[aoc_generator(day20)]
-pub fn input_generator(input: &str) -> (Grid<Track>, (usize, usize)) {
+pub fn input_generator(input: &str) -> RaceTrack {
     let mut entity = Vec::new();
     let width = input.lines().next().unwrap().len();
     let height = input.lines().count();
 
     let mut start = (0, 0);
+    let mut end = (0, 0);
 
     for (row, line) in input.lines().enumerate() {
         for (col, ch) in line.chars().enumerate() {
             let item = match ch {
-                'E' => Track::Path,
+                'E' => { end = (col, row); Track::Path },
                 '.' => Track::Path,
                 'S' => { start = (col, row); Track::Path },
                 '#' => Track::Wall,
@@ -28,95 +159,17 @@ pub fn input_generator(input: &str) -> (Grid<Track>, (usize, usize)) {
         }
     }
 
-    (Grid::new(width, height, entity), start)
+    RaceTrack::new(Grid::new(width, height, entity), start, end)
 }
 
 #[aoc(day20, part1)]
-pub fn solve_part1((track, start): &(Grid<Track>, (usize, usize))) -> usize {
-    let mut course = HashMap::new();
-    let mut race = Vec::from([start.clone()]);
-    let mut poss_sc = Vec::new();
-    let mut idx = 0;
-
-    while let Some(pos) = race.pop() {
-        for point in track.neighbours_as(&pos, Track::Path) {
-            if !course.contains_key(&point) {
-                race.push(point);
-            }
-        }
-        course.insert(pos, idx as usize);
-        idx += 1;
-
-        for (dy, dx) in &ORTHO {
-            let tiles = track.look(&pos, &(*dx, *dy), 2);
-            if tiles.len() == 2 {
-                if tiles[0].1 == Track::Wall && matches!(tiles[1].1, Track::Path) {
-                        poss_sc.push((pos, tiles[1].0));
-                }
-            }
-        }
-    }
-
-    // Tally cheats
-    // let mut cheats = HashMap::new();
-    let mut cheat_total = 0;
-
-    for cheat in poss_sc {
-        let start = course.get(&cheat.0).unwrap();
-        let end = course.get(&cheat.1).unwrap();
-        if end > start {
-            // *cheats.entry(end - start - 2).or_insert(0) += 1;
-            if (end - start - 2) >= 100 {
-                cheat_total += 1;
-            }
-        }
-    }
-
-    // let mut sorted_keys: Vec<&usize> = cheats.keys().collect();
-    // sorted_keys.sort();
-
-    // for key in sorted_keys {
-    //     if let Some(value) = cheats.get(key) {
-    //         println!("There are {} cheat(s) that save {} picoseconds.", value, key);
-    //     }
-    // }
-
-    cheat_total
+pub fn solve_part1(track: &RaceTrack) -> usize {
+    track.analyse().count(2, track.threshold)
 }
 
 #[aoc(day20, part2)]
-pub fn solve_part2((track, start): &(Grid<Track>, (usize, usize))) -> usize {
-    let mut course = HashMap::new();
-    let mut race = Vec::from([start.clone()]);
-    let mut poss_sc = Vec::new();
-    let mut idx = 0;
-
-    while let Some(pos) = race.pop() {
-        for point in track.neighbours_as(&pos, Track::Path) {
-            if !course.contains_key(&point) {
-                race.push(point);
-            }
-        }
-        course.insert(pos, idx as usize);
-        idx += 1;
-
-        let tiles = track.in_range_as(&pos, 20, Track::Path);
-        poss_sc.push((pos, tiles));
-    }
-
-    poss_sc.par_iter()
-        .map(|(pos, sc)| {
-            let start = course.get(&pos).unwrap();
-            let mut count = 0;
-            for (cheat, md) in sc {
-                let end = course.get(&cheat).unwrap();
-                if end > start && (*end as i32 - *start as i32 - *md as i32) >= 100 {
-                    count += 1;
-                }
-            }
-            count
-        })
-        .sum()
+pub fn solve_part2(track: &RaceTrack) -> usize {
+    track.analyse().count(20, track.threshold)
 }
 
 #[cfg(test)]
@@ -141,11 +194,77 @@ mod tests {
 
     #[test]
     fn part1_test() {
-        assert_eq!(solve_part1(&input_generator(TEST)), 10);
+        assert_eq!(solve_part1(&input_generator(TEST).with_threshold(10)), 10);
     }
 
     #[test]
     fn part2_test() {
-        assert_eq!(solve_part2(&input_generator(TEST)), 10);
+        assert_eq!(solve_part2(&input_generator(TEST).with_threshold(50)), 285);
+    }
+
+    #[test]
+    fn histogram_test() {
+        let race = input_generator(TEST).analyse();
+        assert_eq!(race.best, Some(84));
+
+        let short = race.histogram(2, 1);
+        let expected = [(2, 14), (4, 14), (6, 2), (8, 4), (10, 2), (12, 3), (20, 1), (36, 1), (38, 1), (40, 1), (64, 1)];
+        assert_eq!(short, Savings(BTreeMap::from(expected)));
+        assert_eq!(short.total(), race.count(2, 1));
+        assert!(short.to_string().starts_with("There are 14 cheats that save 2 picoseconds.\n"));
+        assert!(short.to_string().ends_with("There is 1 cheat that saves 64 picoseconds.\n"));
+
+        let long = race.histogram(20, 50);
+        assert_eq!(long.0.get(&76), Some(&3));
+        assert_eq!(long.0.get(&50), Some(&32));
+        assert_eq!(long.total(), 285);
+        assert_eq!(race.count(20, 77), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn branching_test() {
+        // Two ways round plus a couple of dead ends
+        const MAZE: &str = "###########
+#S..#.....#
+#.#.#.###.#
+#.#...#...#
+#.#####.###
+#...#...#E#
+###.#.#.#.#
+#.....#...#
+###########";
+
+        let race = input_generator(MAZE).analyse();
+        assert_eq!(race.best, Some(20));
+        assert_eq!(race.from_start[(9usize, 5usize)], race.best);
+        assert_eq!(race.to_end[(1usize, 1usize)], race.best);
+        assert_eq!(race.from_start[(1usize, 7usize)], Some(10));
+        assert_eq!(race.to_end[(1usize, 7usize)], Some(14));
+
+        // Knocking out a single wall is the same as a 2 picosecond cheat through it, so the
+        // histogram should have everything that saves
+        let savings = race.histogram(2, 1);
+        let (width, height) = (MAZE.find('\n').unwrap() + 1, MAZE.lines().count());
+        let mut best_saving = 0;
+        for (i, ch) in MAZE.char_indices().filter(|&(_, ch)| ch == '#') {
+            let (x, y) = (i % width, i / width);
+            if x == 0 || y == 0 || x == width - 2 || y == height - 1 {
+                continue;
+            }
+            let mut maze = MAZE.to_string();
+            maze.replace_range(i..i + ch.len_utf8(), ".");
+            let saved = race.best.unwrap() - input_generator(&maze).analyse().best.unwrap();
+            if saved > 0 {
+                assert!(savings.0.contains_key(&saved));
+            }
+            best_saving = best_saving.max(saved);
+        }
+        assert_eq!(savings.0.keys().max(), Some(&best_saving));
+        assert_eq!(savings.total(), race.count(2, 1));
+
+        // Without a way to the end there's no time to beat
+        let blocked = input_generator(&MAZE.replace("###.#.#.#.#", "###.#.#.###"));
+        assert_eq!(blocked.analyse().best, None);
+        assert_eq!(blocked.analyse().count(20, 1), 0);
+    }
+}
@@ -4,6 +4,27 @@ extern crate aoc_runner_derive;
 
 use aoc_runner_derive::aoc_main;
 
-aoc_main! { lib = aoc_2024 }
+mod solutions {
+    use super::*;
 
-// Check https://github.com/gobanos/cargo-aoc
+    aoc_main! { lib = aoc_2024 }
+
+    pub fn run() {
+        main()
+    }
+}
+
+// `debug17 [input]` opens the day 17 debugger on stdin instead of running every day
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.get(1).map(String::as_str) == Some("debug17") {
+        let path = args.get(2).map(String::as_str).unwrap_or("input/2024/day17.txt");
+        let input = std::fs::read_to_string(path).expect("couldn't read the day 17 input");
+        let (regs, prog) = aoc_2024::day17::input_generator(input.trim_end());
+        let stdin = std::io::stdin();
+        aoc_2024::day17::repl(&regs, &prog, stdin.lock(), &mut std::io::stdout()).unwrap();
+    } else {
+        solutions::run();
+    }
+}
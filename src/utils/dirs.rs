@@ -24,6 +24,16 @@ impl Ortho {
         }
     }
 
+    // (dx, dy) with y growing downwards
+    pub fn delta(&self) -> (i32, i32) {
+        match self {
+            Ortho::North => (0, -1),
+            Ortho::East  => (1, 0),
+            Ortho::South => (0, 1),
+            Ortho::West  => (-1, 0),
+        }
+    }
+
     pub fn iter() -> impl Iterator<Item = Self> {
         [Ortho::North, Ortho::East, Ortho::South, Ortho::West].iter().copied()
     }
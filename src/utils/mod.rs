@@ -1,12 +1,16 @@
 #![allow(unused_imports)]
 mod bitvec;
 mod dirs;
+mod dsu;
 mod grid;
 mod macros;
 mod point;
+mod region;
 
 pub use bitvec::*;
 pub use dirs::*;
+pub use dsu::*;
 pub use grid::*;
 pub use macros::*;
-pub use point::*;
\ No newline at end of file
+pub use point::*;
+pub use region::*;
\ No newline at end of file
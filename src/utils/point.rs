@@ -1,4 +1,6 @@
-#[derive(Debug)]
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
@@ -20,6 +22,35 @@ impl Point {
     pub fn as_utup(&self) -> (usize, usize) {
         (self.x as usize, self.y as usize)
     }
+
+    // Wraps around a torus of the given size, so it always lands in bounds
+    pub fn wrap(&self, boundary: (i32, i32)) -> Self {
+        Self { x: self.x.rem_euclid(boundary.0), y: self.y.rem_euclid(boundary.1) }
+    }
+}
+
+impl Add for Point {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+
+impl Sub for Point {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self { x: self.x - other.x, y: self.y - other.y }
+    }
+}
+
+impl Mul<i32> for Point {
+    type Output = Self;
+
+    fn mul(self, scale: i32) -> Self {
+        Self { x: self.x * scale, y: self.y * scale }
+    }
 }
 
 pub trait Boundary<T> {
@@ -37,4 +68,4 @@ impl Boundary<(usize, usize)> for Point {
     fn in_bounds(&self, boundary: (usize, usize)) -> bool {
         (self.x as usize).lt(&boundary.0) && (self.y as usize).lt(&boundary.1)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,127 @@
+use crate::prelude::*;
+
+// A 4-connected patch of identical cells
+#[derive(Debug, Clone)]
+pub struct Region<T> {
+    pub label: T,
+    pub cells: Vec<(usize, usize)>,
+    pub area: usize,
+    pub perimeter: usize,
+    // Straight edges, counted as corners since every polygon has as many of each
+    pub sides: usize,
+    // Enclosed pockets of other cells, however many regions are nested inside them
+    pub holes: usize,
+    // Inclusive (min, max) corners as (x, y)
+    pub bounds: ((usize, usize), (usize, usize)),
+}
+
+impl<T: Clone + Copy + PartialEq> Grid<T> {
+    // Labels connected components with union-find, then measures each one
+    pub fn regions(&self) -> Vec<Region<T>> {
+        let mut components = UnionFind::new(self.entity.len());
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = y * self.width + x;
+                if x + 1 < self.width && self.entity[idx] == self.entity[idx + 1] {
+                    components.union(idx, idx + 1);
+                }
+                if y + 1 < self.height && self.entity[idx] == self.entity[idx + self.width] {
+                    components.union(idx, idx + self.width);
+                }
+            }
+        }
+
+        // Row-major order of first appearance
+        let mut slots = HashMap::new();
+        let mut members: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut labels = vec![0; self.entity.len()];
+        for (idx, label) in labels.iter_mut().enumerate() {
+            let root = components.find(idx);
+            let slot = *slots.entry(root).or_insert_with(|| {
+                members.push(Vec::new());
+                members.len() - 1
+            });
+            members[slot].push((idx % self.width, idx / self.width));
+            *label = slot;
+        }
+
+        members.into_iter()
+            .enumerate()
+            .map(|(slot, cells)| self.measure(slot, cells, &labels))
+            .collect()
+    }
+
+    fn measure(&self, slot: usize, cells: Vec<(usize, usize)>, labels: &[usize]) -> Region<T> {
+        let inside = |x: i32, y: i32| {
+            x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height &&
+            labels[y as usize * self.width + x as usize] == slot
+        };
+
+        let mut perimeter = 0;
+        let mut sides = 0;
+        for &(x, y) in &cells {
+            let (x, y) = (x as i32, y as i32);
+            perimeter += ORTHO.iter().filter(|(dx, dy)| !inside(x + dx, y + dy)).count();
+
+            // Outer corners have both edges open, inner ones only the diagonal
+            for (dx, dy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                let horizontal = inside(x + dx, y);
+                let vertical = inside(x, y + dy);
+                if (!horizontal && !vertical) || (horizontal && vertical && !inside(x + dx, y + dy)) {
+                    sides += 1;
+                }
+            }
+        }
+
+        let min_x = cells.iter().map(|c| c.0).min().unwrap();
+        let max_x = cells.iter().map(|c| c.0).max().unwrap();
+        let min_y = cells.iter().map(|c| c.1).min().unwrap();
+        let max_y = cells.iter().map(|c| c.1).max().unwrap();
+
+        // Join everything outside the region within a box one cell larger than its bounds,
+        // 8-connected so pockets that only touch diagonally are the same hole. Anything not
+        // joined to the frame is enclosed.
+        let (width, height) = (max_x - min_x + 3, max_y - min_y + 3);
+        let outside = |bx: usize, by: usize| !inside((bx + min_x) as i32 - 1, (by + min_y) as i32 - 1);
+        let frame = width * height;
+        let mut pockets = UnionFind::new(frame + 1);
+        for by in 0..height {
+            for bx in 0..width {
+                if !outside(bx, by) {
+                    continue;
+                }
+                let idx = by * width + bx;
+                if bx == 0 || by == 0 || bx == width - 1 || by == height - 1 {
+                    pockets.union(idx, frame);
+                }
+                for (dx, dy) in [(1, 0), (0, 1), (1, 1), (-1, 1)] {
+                    let (nx, ny) = (bx as i32 + dx, by as i32 + dy);
+                    if nx >= 0 && (nx as usize) < width && (ny as usize) < height &&
+                       outside(nx as usize, ny as usize) {
+                        pockets.union(idx, ny as usize * width + nx as usize);
+                    }
+                }
+            }
+        }
+        let mut roots = HashSet::new();
+        for by in 0..height {
+            for bx in 0..width {
+                if outside(bx, by) && !pockets.connected(by * width + bx, frame) {
+                    roots.insert(pockets.find(by * width + bx));
+                }
+            }
+        }
+
+        let (x, y) = cells[0];
+        Region {
+            label: self.entity[y * self.width + x],
+            area: cells.len(),
+            cells,
+            perimeter,
+            sides,
+            holes: roots.len(),
+            bounds: ((min_x, min_y), (max_x, max_y)),
+        }
+    }
+}